@@ -0,0 +1,49 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use pc_assist::config::SolverConfig;
+use pc_assist::plan::{PlanPiceState, PlanPlacement};
+use pc_assist::solver;
+
+mod fixtures;
+
+fn board(c: &mut Criterion) {
+    for (name, board) in fixtures::boards() {
+        c.bench_function(&format!("get_bitboard {}", name), |b| {
+            b.iter(|| black_box(&board).get_bitboard())
+        });
+        c.bench_function(&format!("get_queue {}", name), |b| {
+            b.iter(|| black_box(&board).get_queue())
+        });
+    }
+}
+
+fn plan(c: &mut Criterion) {
+    let config = SolverConfig {
+        threads: 1,
+        timeout_ms: None,
+        ..SolverConfig::default()
+    };
+    for (name, board) in fixtures::boards() {
+        // Boards without a solution have no placements to convert.
+        let placements = match solver::solve(&board, &config, |_| {}).1 {
+            Some(placements) => placements,
+            None => continue,
+        };
+        c.bench_function(&format!("PlanPlacement::cells {}", name), |b| {
+            b.iter(|| {
+                for placement in black_box(&placements) {
+                    black_box(PlanPlacement::cells(placement));
+                }
+            })
+        });
+        c.bench_function(&format!("PlanPiceState::cells {}", name), |b| {
+            b.iter(|| {
+                for placement in black_box(&placements) {
+                    black_box(PlanPiceState::cells(&placement.kind));
+                }
+            })
+        });
+    }
+}
+
+criterion_group!(benches, board, plan);
+criterion_main!(benches);
@@ -0,0 +1,15 @@
+use pc_assist::board::Board;
+
+/// Recorded boards in the `Board` text format.
+pub const FIXTURES: [(&str, &str); 3] = [
+    ("empty", include_str!("empty.txt")),
+    ("mid_pc", include_str!("mid_pc.txt")),
+    ("no_solution", include_str!("no_solution.txt")),
+];
+
+pub fn boards() -> Vec<(&'static str, Board)> {
+    FIXTURES
+        .iter()
+        .map(|&(name, text)| (name, text.parse().unwrap()))
+        .collect()
+}
@@ -0,0 +1,56 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use pc_assist::config::SolverConfig;
+use pc_assist::solver;
+
+mod fixtures;
+
+fn solve_pc(c: &mut Criterion) {
+    for (name, board) in fixtures::boards() {
+        let queue = board.get_queue();
+        let bitboard = board.get_bitboard();
+        c.bench_function(&format!("solve_pc {}", name), |b| {
+            b.iter(|| {
+                let mut found = false;
+                pcf::solve_pc(
+                    &queue,
+                    bitboard,
+                    true,
+                    true,
+                    pcf::placeability::simple_srs_spins,
+                    |_| {
+                        found = true;
+                        pcf::SearchStatus::Abort
+                    },
+                );
+                found
+            })
+        });
+    }
+}
+
+/// `solver::solve` on one thread and on every core, to see what splitting the search gains.
+fn threads(c: &mut Criterion) {
+    let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let mut counts = vec![1, cores];
+    counts.dedup();
+    for (name, board) in fixtures::boards() {
+        for &threads in &counts {
+            let config = SolverConfig {
+                threads,
+                timeout_ms: None,
+                ..SolverConfig::default()
+            };
+            c.bench_function(&format!("solve {} {} threads", name, threads), |b| {
+                b.iter(|| solver::solve(&board, &config, |_| {}).0)
+            });
+        }
+    }
+}
+
+criterion_group! {
+    name = benches;
+    // A search takes milliseconds to seconds, so fewer samples keep the run short.
+    config = Criterion::default().sample_size(10);
+    targets = solve_pc, threads
+}
+criterion_main!(benches);
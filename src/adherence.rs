@@ -0,0 +1,92 @@
+use crate::history::Move;
+use crate::plan::Cells;
+use serde::{Deserialize, Serialize};
+
+/// Counts how often the player placed a piece where the overlay suggested.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Adherence {
+    pub followed: u32,
+    pub deviated: u32,
+}
+
+impl Adherence {
+    pub fn new() -> Adherence {
+        Adherence {
+            followed: 0,
+            deviated: 0,
+        }
+    }
+
+    /// Records the locked move against the suggestion that was on screen and returns
+    /// whether the player followed it.
+    pub fn record(&mut self, suggested: &Cells, m: &Move) -> bool {
+        let mut expected: Vec<_> = suggested.iter().map(|&(x, y, _)| (x, y)).collect();
+        let mut actual = m.cells().to_vec();
+        expected.sort();
+        actual.sort();
+
+        let followed = expected == actual;
+        if followed {
+            self.followed += 1;
+        } else {
+            self.deviated += 1;
+        }
+        followed
+    }
+
+    pub fn percentage(&self) -> Option<f64> {
+        let total = self.followed + self.deviated;
+        if total == 0 {
+            None
+        } else {
+            Some(self.followed as f64 * 100.0 / total as f64)
+        }
+    }
+}
+
+impl Default for Adherence {
+    fn default() -> Self {
+        Adherence::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{Board, HEIGHT, WIDTH};
+    use crate::history::History;
+    use crate::plan::Step;
+    use crate::test_util::placements;
+
+    #[test]
+    fn locking_on_the_suggested_cells_follows_the_plan() {
+        for (i, &piece) in pcf::PIECES.iter().enumerate() {
+            let next = (i as u32 + 1) % 7;
+            let prev = Board {
+                columns: vec![vec![-1; HEIGHT]; WIDTH],
+                current_piece: Some(i as u32),
+                hold: None,
+                next_pieces: vec![next; 5],
+            };
+            for placement in placements(pcf::BitBoard(0), &[piece; 10]) {
+                let step = Step::from_placement(&placement);
+                let mut now = prev.clone();
+                for &(x, y) in &step.cells {
+                    now.columns[x as usize][y as usize] = i as i32;
+                }
+                now.current_piece = Some(next);
+                now.next_pieces.push(i as u32);
+
+                let m = History::new()
+                    .update(&prev, &now, &now.diff(&prev))
+                    .unwrap_or_else(|| panic!("no move found for {:?}", placement));
+                assert!(
+                    Adherence::new().record(&step.cells(), &m),
+                    "{:?} locked as {:?}",
+                    placement,
+                    m
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,57 @@
+//! Plays seeded games in the simulator, following the suggestions, and fails when the
+//! tracked queue goes wrong or no PC is suggested in time.
+//!
+//! Usage: `harness <seed> <pieces> [--expect-pc-by <pieces>]`
+
+use pc_assist::config::SolverConfig;
+use pc_assist::harness::{self, Script};
+use pc_assist::sim::Simulator;
+use std::io::{Error, ErrorKind};
+
+fn main() -> std::io::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let usage = || {
+        Error::new(
+            ErrorKind::InvalidInput,
+            "usage: harness <seed> <pieces> [--expect-pc-by <pieces>]",
+        )
+    };
+    let number = |s: &String| s.parse::<u64>().map_err(|_| usage());
+    let (seed, pieces, expect_pc_by) = match &args[..] {
+        [seed, pieces] => (number(seed)?, number(pieces)?, None),
+        [seed, pieces, flag, by] if flag == "--expect-pc-by" => {
+            (number(seed)?, number(pieces)?, Some(number(by)? as usize))
+        }
+        _ => return Err(usage()),
+    };
+
+    let config = SolverConfig {
+        timeout_ms: None,
+        ..SolverConfig::default()
+    };
+    let report = harness::play(
+        Simulator::new(seed),
+        pieces as usize,
+        &Script::FollowSuggestions,
+        &config,
+    );
+    println!("{:?}", report);
+
+    let mut failures = vec![];
+    if !report.queue_errors.is_empty() {
+        failures.push(format!(
+            "wrong queue after pieces {:?}",
+            report.queue_errors
+        ));
+    }
+    if let Some(by) = expect_pc_by {
+        if report.first_suggestion.map_or(true, |at| at > by) {
+            failures.push(format!("no PC suggested by piece {}", by));
+        }
+    }
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::other(failures.join(", ")))
+    }
+}
@@ -0,0 +1,55 @@
+//! Draws a board in the text format of `Board`, with the first step of its solution, to a
+//! PNG the way the overlay would show it. The plan is drawn with the theme in
+//! `pc_assist.json`; `--full-path` draws every step.
+//!
+//! Usage: `render_board [--full-path] <board.txt> <out.png> [width height]`
+
+use pc_assist::board::Board;
+use pc_assist::config::{Config, SolverConfig, CONFIG_PATH};
+use pc_assist::layout::FieldPlacement;
+use pc_assist::plan::Step;
+use pc_assist::render::Renderer;
+use pc_assist::solver;
+use std::io::{Error, ErrorKind};
+
+fn main() -> std::io::Result<()> {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let full_path = args.first().map_or(false, |a| a == "--full-path");
+    if full_path {
+        args.remove(0);
+    }
+    let usage = || {
+        Error::new(
+            ErrorKind::InvalidInput,
+            "usage: render_board [--full-path] <board.txt> <out.png> [width height]",
+        )
+    };
+    let (input, output) = match &args[..] {
+        [input, output, ..] => (input, output),
+        _ => return Err(usage()),
+    };
+    let (width, height) = match &args[2..] {
+        [] => (1920, 1080),
+        [width, height] => (
+            width.parse().map_err(|_| usage())?,
+            height.parse().map_err(|_| usage())?,
+        ),
+        _ => return Err(usage()),
+    };
+
+    let board: Board = std::fs::read_to_string(input)?
+        .parse()
+        .map_err(|e| Error::new(ErrorKind::InvalidData, format!("{}", e)))?;
+    let config = SolverConfig {
+        timeout_ms: None,
+        ..SolverConfig::default()
+    };
+    let plan: Vec<Step> = solver::solve(&board, &config, |_| {})
+        .1
+        .map(|s| s.iter().map(Step::from_placement).collect())
+        .unwrap_or_default();
+
+    let mut renderer = Renderer::new(width, height, FieldPlacement::PLAYER_1);
+    renderer.set_theme(Config::load(CONFIG_PATH)?.theme)?;
+    renderer.save_png(output, &board, &plan, full_path)
+}
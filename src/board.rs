@@ -1,6 +1,30 @@
 extern crate pcf;
 
-#[derive(Clone, Debug)]
+use crate::history::Move;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+pub const WIDTH: usize = 10;
+pub const HEIGHT: usize = 40;
+/// Rows of the field that `pcf::BitBoard` holds.
+pub const BITBOARD_ROWS: usize = 6;
+
+/// Field coordinates, shared by the board, `pcf` bitboards, plans and the overlay: `x`
+/// counts columns from the left wall and `y` rows up from the bottom row, so a cell is
+/// `columns[x][y]` and bit `y * WIDTH + x` of a `pcf::BitBoard`.
+pub fn bitboard_bit(x: i32, y: i32) -> u64 {
+    1 << (y * WIDTH as i32 + x)
+}
+
+/// The cell of bit `bit` of a `pcf::BitBoard`.
+pub fn bit_position(bit: u32) -> (i32, i32) {
+    ((bit as usize % WIDTH) as i32, (bit as usize / WIDTH) as i32)
+}
+
+const PIECE_NAMES: [char; 7] = ['S', 'Z', 'J', 'L', 'T', 'O', 'I'];
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Board {
     pub columns: Vec<Vec<i32>>,
     pub current_piece: Option<u32>,
@@ -11,31 +35,306 @@ pub struct Board {
 impl Board {
     pub fn get_queue(&self) -> Vec<pcf::Piece> {
         let mut queue = self.next_pieces.clone();
-        self.hold.and_then(|i| Some(queue.insert(0, i)));
-        self.current_piece.and_then(|i| Some(queue.insert(0, i)));
+        if let Some(i) = self.hold {
+            queue.insert(0, i);
+        }
+        if let Some(i) = self.current_piece {
+            queue.insert(0, i);
+        }
 
-        println!("queue: {:?}", queue);
-        return queue.into_iter().map(|i| pcf::PIECES[i as usize]).collect();
+        queue.into_iter().map(|i| pcf::PIECES[i as usize]).collect()
     }
 
     pub fn get_bitboard(&self) -> pcf::BitBoard {
         let mut bits: u64 = 0b0;
-        for y in (0..20).rev() {
-            bits <<= 10;
-            let mut row: u64 = 0;
-            for x in (0..10).rev() {
-                row <<= 1;
-                if -1 != self.columns[x][y] {
-                    row += 1;
+        for (x, column) in self.columns.iter().enumerate() {
+            for (y, &cell) in column.iter().enumerate().take(BITBOARD_ROWS) {
+                if cell != -1 {
+                    bits |= bitboard_bit(x as i32, y as i32);
                 }
             }
-            bits += row;
         }
-        return pcf::BitBoard(bits);
+        pcf::BitBoard(bits)
+    }
+
+    /// Height of the highest non-empty row, 0 for an empty field.
+    pub fn height(&self) -> usize {
+        (0..HEIGHT)
+            .rev()
+            .find(|&y| self.columns.iter().any(|column| column[y] != -1))
+            .map_or(0, |y| y + 1)
+    }
+
+    fn filled_cells(&self) -> u32 {
+        self.columns
+            .iter()
+            .map(|column| column.iter().filter(|&&c| c != -1).count() as u32)
+            .sum()
+    }
+
+    fn garbage_rows(&self) -> u32 {
+        (0..HEIGHT)
+            .filter(|&y| self.columns.iter().any(|column| column[y] == 7))
+            .count() as u32
+    }
+
+    /// Reports what changed since `prev`, which should be the previous snapshot of the
+    /// same player.
+    ///
+    /// Garbage rows are assumed to have a single hole, so each one adds 9 cells.
+    pub fn diff(&self, prev: &Board) -> BoardDiff {
+        let garbage_received = self.garbage_rows().saturating_sub(prev.garbage_rows());
+        let expected = prev.filled_cells() + 9 * garbage_received;
+        let filled = self.filled_cells();
+
+        let piece_locked = self.columns != prev.columns && filled != expected;
+        let lines_cleared = if piece_locked && expected + 4 >= filled {
+            (expected + 4 - filled) / 10
+        } else {
+            0
+        };
+
+        let piece_changed = self.current_piece != prev.current_piece;
+        let queue_advanced = self.next_pieces != prev.next_pieces;
+        // Swapping for a held piece of the same type leaves the hold as it was, so a hold is
+        // also told from the current piece: it changed while the queue stayed, or it is the
+        // piece that was held rather than the next one.
+        let from_hold = prev.hold.is_some()
+            && self.current_piece == prev.hold
+            && self.current_piece != prev.next_pieces.first().copied();
+        let hold_used = self.hold != prev.hold || (piece_changed && (!queue_advanced || from_hold));
+
+        BoardDiff {
+            piece_changed,
+            piece_locked,
+            lines_cleared,
+            hold_used,
+            queue_advanced,
+            garbage_received,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoardDiff {
+    pub piece_changed: bool,
+    pub piece_locked: bool,
+    pub lines_cleared: u32,
+    pub hold_used: bool,
+    pub queue_advanced: bool,
+    pub garbage_received: u32,
+}
+
+fn cell_to_char(cell: i32) -> char {
+    match cell {
+        0..=6 => PIECE_NAMES[cell as usize],
+        7 => 'X',
+        8 => '*',
+        -1 => '.',
+        // Codes the game should not produce are kept visible instead of printed as holes,
+        // and do not parse back.
+        _ => '?',
+    }
+}
+
+fn char_to_cell(c: char) -> Option<i32> {
+    match c {
+        '.' => Some(-1),
+        'X' => Some(7),
+        '*' => Some(8),
+        _ => char_to_piece(c).map(|i| i as i32),
+    }
+}
+
+fn piece_to_char(piece: Option<u32>) -> char {
+    piece.map_or('-', |i| PIECE_NAMES[i as usize])
+}
+
+fn char_to_piece(c: char) -> Option<u32> {
+    PIECE_NAMES.iter().position(|&p| p == c).map(|i| i as u32)
+}
+
+/// Text form used for bug reports and fixtures:
+///
+/// ```text
+/// hold: T
+/// current: I
+/// next: SZOJL
+/// ..........
+/// LLL...SS..
+/// LJJJ.SSOO.
+/// ```
+///
+/// Field rows are printed from the highest non-empty row down to row 0.
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "hold: {}", piece_to_char(self.hold))?;
+        writeln!(f, "current: {}", piece_to_char(self.current_piece))?;
+        let next: String = self
+            .next_pieces
+            .iter()
+            .map(|&i| piece_to_char(Some(i)))
+            .collect();
+        writeln!(f, "next: {}", next)?;
+        for y in (0..self.height()).rev() {
+            let row: String = self.columns.iter().map(|c| cell_to_char(c[y])).collect();
+            writeln!(f, "{}", row)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseBoardError(String);
+
+impl fmt::Display for ParseBoardError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid board: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseBoardError {}
+
+impl FromStr for Board {
+    type Err = ParseBoardError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut board = Board {
+            columns: vec![vec![-1; HEIGHT]; WIDTH],
+            current_piece: None,
+            hold: None,
+            next_pieces: vec![],
+        };
+        let mut rows = vec![];
+
+        for line in s.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            let (key, value) = match line.find(':') {
+                Some(i) => (&line[..i], line[i + 1..].trim()),
+                None => {
+                    rows.push(line);
+                    continue;
+                }
+            };
+            let parse_piece = |v: &str| match v {
+                "-" | "" => Ok(None),
+                _ if v.chars().count() == 1 => char_to_piece(v.chars().next().unwrap())
+                    .map(Some)
+                    .ok_or_else(|| ParseBoardError(format!("unknown piece {:?}", v))),
+                _ => Err(ParseBoardError(format!("expected one piece, got {:?}", v))),
+            };
+            match key {
+                "hold" => board.hold = parse_piece(value)?,
+                "current" => board.current_piece = parse_piece(value)?,
+                "next" => {
+                    board.next_pieces = value
+                        .chars()
+                        .map(|c| {
+                            char_to_piece(c)
+                                .ok_or_else(|| ParseBoardError(format!("unknown piece {:?}", c)))
+                        })
+                        .collect::<Result<_, _>>()?
+                }
+                _ => return Err(ParseBoardError(format!("unknown key {:?}", key))),
+            }
+        }
+
+        if rows.len() > HEIGHT {
+            return Err(ParseBoardError(format!("too many rows ({})", rows.len())));
+        }
+        for (y, row) in rows.iter().rev().enumerate() {
+            if row.chars().count() != WIDTH {
+                return Err(ParseBoardError(format!(
+                    "row {:?} is not {} wide",
+                    row, WIDTH
+                )));
+            }
+            for (x, c) in row.chars().enumerate() {
+                board.columns[x][y] = char_to_cell(c)
+                    .ok_or_else(|| ParseBoardError(format!("unknown cell {:?}", c)))?;
+            }
+        }
+
+        Ok(board)
     }
 }
 
 pub enum BoardEvent {
     Exit,
     Continue(Board),
+    Lock(Move),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "hold: T\ncurrent: I\nnext: SZOJL\nX*........\nLLL...SS..\nLJJJ.SSOO.\n";
+
+    #[test]
+    fn text_round_trip() {
+        let board: Board = TEXT.parse().unwrap();
+        assert_eq!(board.hold, Some(4));
+        assert_eq!(board.current_piece, Some(6));
+        assert_eq!(board.next_pieces, vec![0, 1, 5, 2, 3]);
+        assert_eq!(board.columns[0][0], 3);
+        assert_eq!(board.columns[1][2], 8);
+        assert_eq!(board.to_string(), TEXT);
+        assert_eq!(board.to_string().parse::<Board>(), Ok(board));
+    }
+
+    #[test]
+    fn every_cell_code_round_trips() {
+        let mut board: Board = "".parse().unwrap();
+        for (x, code) in (-1..=8).enumerate() {
+            board.columns[x][0] = code;
+        }
+        assert_eq!(board.to_string().parse::<Board>(), Ok(board));
+    }
+
+    #[test]
+    fn diff_reports_a_lock_when_the_next_piece_has_the_same_type() {
+        let prev: Board = "current: S\nnext: SZOJL\n".parse().unwrap();
+        let now: Board = "current: S\nnext: ZOJLT\n.SS.......\nSS........\n"
+            .parse()
+            .unwrap();
+        let diff = now.diff(&prev);
+        assert!(!diff.piece_changed);
+        assert!(diff.piece_locked);
+        assert!(diff.queue_advanced);
+        assert!(!diff.hold_used);
+        assert_eq!(diff.lines_cleared, 0);
+        assert_eq!(diff.garbage_received, 0);
+    }
+
+    #[test]
+    fn diff_reports_a_hold_that_keeps_the_held_type() {
+        // The current piece changed but the queue did not move.
+        let prev: Board = "hold: T\ncurrent: S\nnext: ZOJLI\n".parse().unwrap();
+        let now: Board = "hold: T\ncurrent: T\nnext: ZOJLI\n".parse().unwrap();
+        let diff = now.diff(&prev);
+        assert!(diff.hold_used);
+        assert!(!diff.piece_locked);
+
+        // The queue moved, but the current piece is the held one and not the next.
+        let now: Board = "hold: T\ncurrent: T\nnext: OJLIS\n".parse().unwrap();
+        assert!(now.diff(&prev).hold_used);
+
+        // The next piece has the held type, so it came from the queue.
+        let prev: Board = "hold: T\ncurrent: S\nnext: TOJLI\n".parse().unwrap();
+        let now: Board = "hold: T\ncurrent: T\nnext: OJLIZ\n.SS.......\nSS........\n"
+            .parse()
+            .unwrap();
+        let diff = now.diff(&prev);
+        assert!(diff.piece_locked);
+        assert!(!diff.hold_used);
+    }
+
+    #[test]
+    fn unknown_cell_codes_do_not_become_holes() {
+        let mut board: Board = "".parse().unwrap();
+        board.columns[3][0] = 9;
+        let text = board.to_string();
+        assert!(text.ends_with("...?......\n"), "{}", text);
+        assert!(text.parse::<Board>().is_err());
+    }
 }
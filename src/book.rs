@@ -0,0 +1,309 @@
+//! Opening book of perfect clears for empty boards.
+//!
+//! Entries are keyed on the first 6 pieces of the queue, the current piece and the 5
+//! previews the game shows, and each solution is found from those 6 pieces alone. PCs that
+//! need more pieces are not in the book and are left to the solver.
+
+use crate::board::{Board, HEIGHT, WIDTH};
+use crate::config::SolverConfig;
+use crate::plan::Step;
+use crate::solver;
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind, Read, Result, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 8] = b"PCBOOK2\n";
+
+/// Pieces a key is built from: the current piece and the 5 previews the game shows.
+const KEY_LEN: usize = 6;
+
+/// Solutions for empty boards, generated offline with `Book::build`.
+pub struct Book {
+    /// Solver options the book was generated with.
+    options: String,
+    hold: bool,
+    entries: HashMap<u64, Vec<Step>>,
+}
+
+fn solver_options(config: &SolverConfig) -> String {
+    format!(
+        "{:?} {:?} {:?}",
+        config.hold, config.placeability, config.max_pieces
+    )
+}
+
+/// The key of the first `KEY_LEN` pieces of `queue`, 3 bits per piece, `None` if the
+/// queue is shorter.
+fn key(queue: &[u32]) -> Option<u64> {
+    let prefix = queue.get(..KEY_LEN)?;
+    Some(
+        prefix
+            .iter()
+            .enumerate()
+            .fold(0, |key, (i, &p)| key | (p as u64 + 1) << (3 * i)),
+    )
+}
+
+fn permutations(pieces: &[u32], len: usize) -> Vec<Vec<u32>> {
+    if len == 0 {
+        return vec![vec![]];
+    }
+    let mut result = vec![];
+    for (i, &p) in pieces.iter().enumerate() {
+        let mut rest = pieces.to_vec();
+        rest.remove(i);
+        for mut tail in permutations(&rest, len - 1) {
+            tail.insert(0, p);
+            result.push(tail);
+        }
+    }
+    result
+}
+
+/// Queues seen at the start of the first and second PC of a game: a whole bag, and the 4
+/// pieces left over from the second bag followed by 2 pieces of the third.
+fn opening_queues() -> Vec<Vec<u32>> {
+    let bag: Vec<u32> = (0..7).collect();
+    let mut queues = permutations(&bag, 7);
+    for leftover in permutations(&bag, 4) {
+        for next in permutations(&bag, 2) {
+            queues.push(leftover.iter().chain(next.iter()).copied().collect());
+        }
+    }
+    queues
+}
+
+fn encode_step(step: &Step, out: &mut Vec<u8>) -> Result<()> {
+    out.push(step.piece as u8);
+    for &(x, y) in &step.cells {
+        if !(0..WIDTH as i32).contains(&x) || !(0..25).contains(&y) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("cell {:?} of {:?} does not fit in a book", (x, y), step),
+            ));
+        }
+        out.push((y * WIDTH as i32 + x) as u8);
+    }
+    Ok(())
+}
+
+fn decode_step(data: &[u8]) -> Step {
+    let mut cells = [(0, 0); 4];
+    for (c, &b) in cells.iter_mut().zip(data[1..5].iter()) {
+        *c = ((b as usize % WIDTH) as i32, (b as usize / WIDTH) as i32);
+    }
+    Step {
+        piece: data[0] as u32,
+        cells,
+    }
+}
+
+fn read_u32(r: &mut impl Read) -> Result<u32> {
+    let mut buf = [0; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+impl Book {
+    /// Solves the first `KEY_LEN` pieces of every opening queue with `config`, so a
+    /// solution only depends on the pieces in its key. `progress` gets the number of
+    /// queues done and the total.
+    pub fn build(config: &SolverConfig, mut progress: impl FnMut(usize, usize)) -> Book {
+        let queues = opening_queues();
+        let mut entries = HashMap::new();
+        for (i, queue) in queues.iter().enumerate() {
+            let key = match key(queue) {
+                Some(key) if !entries.contains_key(&key) => key,
+                _ => {
+                    progress(i + 1, queues.len());
+                    continue;
+                }
+            };
+            let board = Board {
+                columns: vec![vec![-1; HEIGHT]; WIDTH],
+                current_piece: Some(queue[0]),
+                hold: None,
+                next_pieces: queue[1..KEY_LEN].to_vec(),
+            };
+            let soln = solver::solve(&board, config, |_| {})
+                .1
+                .map(|s| s.iter().map(Step::from_placement).collect::<Vec<_>>());
+            if let Some(steps) = soln {
+                entries.insert(key, steps);
+            }
+            progress(i + 1, queues.len());
+        }
+        Book {
+            options: solver_options(config),
+            hold: config.hold,
+            entries,
+        }
+    }
+
+    /// The stored solution for `board`, which must be empty.
+    ///
+    /// With hold, a held piece can be played as if it were the current piece and the
+    /// current piece came next, so a board with a held piece uses the entry of that queue.
+    pub fn lookup(&self, board: &Board) -> Option<Vec<Step>> {
+        if board.height() != 0 || (board.hold.is_some() && !self.hold) {
+            return None;
+        }
+        let queue: Vec<u32> = board
+            .hold
+            .into_iter()
+            .chain(board.current_piece)
+            .chain(board.next_pieces.iter().copied())
+            .collect();
+        self.entries.get(&key(&queue)?).cloned()
+    }
+
+    /// Loads a book, failing if it was generated with different solver options.
+    pub fn load(path: impl AsRef<Path>, config: &SolverConfig) -> Result<Book> {
+        let mut r = std::io::BufReader::new(std::fs::File::open(path)?);
+        let invalid = |message: &str| Error::new(ErrorKind::InvalidData, message.to_string());
+
+        let mut magic = [0; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a book file"));
+        }
+        let mut options = vec![0; read_u32(&mut r)? as usize];
+        r.read_exact(&mut options)?;
+        let options = String::from_utf8(options).map_err(|_| invalid("bad options"))?;
+        if options != solver_options(config) {
+            return Err(invalid("book was generated with other solver options"));
+        }
+
+        let mut entries = HashMap::new();
+        for _ in 0..read_u32(&mut r)? {
+            let mut key = [0; 8];
+            r.read_exact(&mut key)?;
+            let mut len = [0; 1];
+            r.read_exact(&mut len)?;
+            let mut data = vec![0; len[0] as usize * 5];
+            r.read_exact(&mut data)?;
+            entries.insert(
+                u64::from_le_bytes(key),
+                data.chunks(5).map(decode_step).collect(),
+            );
+        }
+        Ok(Book {
+            options,
+            hold: config.hold,
+            entries,
+        })
+    }
+
+    /// Writes the book as the options it was built with followed by one record per
+    /// queue: the key, the number of steps and 5 bytes per step (piece and cell indices).
+    /// Records are sorted by key, so the same book always gives the same file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut data = MAGIC.to_vec();
+        data.extend_from_slice(&(self.options.len() as u32).to_le_bytes());
+        data.extend_from_slice(self.options.as_bytes());
+
+        let mut keys: Vec<_> = self.entries.keys().copied().collect();
+        keys.sort_unstable();
+        data.extend_from_slice(&(keys.len() as u32).to_le_bytes());
+        for key in keys {
+            let steps = &self.entries[&key];
+            data.extend_from_slice(&key.to_le_bytes());
+            data.push(steps.len() as u8);
+            for step in steps {
+                encode_step(step, &mut data)?;
+            }
+        }
+
+        std::fs::File::create(path)?.write_all(&data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(piece: u32, cells: [(i32, i32); 4]) -> Step {
+        Step { piece, cells }
+    }
+
+    fn book(entries: &[(&[u32], Vec<Step>)]) -> Book {
+        Book {
+            options: solver_options(&SolverConfig::default()),
+            hold: true,
+            entries: entries
+                .iter()
+                .map(|(queue, steps)| (key(queue).unwrap(), steps.clone()))
+                .collect(),
+        }
+    }
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("pc_assist_{}_{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let i = step(6, [(0, 0), (1, 0), (2, 0), (3, 0)]);
+        let o = step(5, [(4, 0), (5, 0), (4, 1), (5, 1)]);
+        let first = book(&[
+            (&[6, 5, 4, 3, 2, 1], vec![i, o]),
+            (&[0, 1, 2, 3, 4, 5], vec![o]),
+            (&[4, 4, 4, 4, 4, 4], vec![]),
+        ]);
+        let path = temp_path("book");
+        first.save(&path).unwrap();
+        let loaded = Book::load(&path, &SolverConfig::default()).unwrap();
+        assert_eq!(loaded.entries, first.entries);
+        assert_eq!(loaded.options, first.options);
+
+        // The same entries inserted in another order give the same file.
+        let data = std::fs::read(&path).unwrap();
+        let second = book(&[
+            (&[4, 4, 4, 4, 4, 4], vec![]),
+            (&[0, 1, 2, 3, 4, 5], vec![o]),
+            (&[6, 5, 4, 3, 2, 1], vec![i, o]),
+        ]);
+        second.save(&path).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), data);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn save_fails_on_steps_outside_the_board() {
+        let wide = step(6, [(8, 0), (9, 0), (10, 0), (11, 0)]);
+        let path = temp_path("bad_book");
+        let err = book(&[(&[6, 5, 4, 3, 2, 1], vec![wide])])
+            .save(&path)
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn lookup_uses_the_queue_prefix_and_hold() {
+        let o = step(5, [(4, 0), (5, 0), (4, 1), (5, 1)]);
+        let book = book(&[(&[6, 5, 4, 3, 2, 1], vec![o])]);
+        let board = |hold: Option<u32>, current: u32, next: &[u32]| Board {
+            columns: vec![vec![-1; HEIGHT]; WIDTH],
+            current_piece: Some(current),
+            hold,
+            next_pieces: next.to_vec(),
+        };
+        assert_eq!(
+            book.lookup(&board(None, 6, &[5, 4, 3, 2, 1])),
+            Some(vec![o])
+        );
+        // Pieces past the key don't matter.
+        assert_eq!(
+            book.lookup(&board(None, 6, &[5, 4, 3, 2, 1, 0])),
+            Some(vec![o])
+        );
+        // A held I plays like a current I with the O next.
+        assert_eq!(
+            book.lookup(&board(Some(6), 5, &[4, 3, 2, 1, 0])),
+            Some(vec![o])
+        );
+        assert_eq!(book.lookup(&board(None, 6, &[5, 4, 3])), None);
+        assert_eq!(book.lookup(&board(Some(0), 6, &[5, 4, 3, 2, 1])), None);
+    }
+}
@@ -0,0 +1,123 @@
+extern crate serde_json;
+
+use crate::board::Board;
+use crate::config::SolverConfig;
+use crate::history::Move;
+use crate::plan::Step;
+use crate::srs::Field;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CacheKey {
+    pub board: u64,
+    /// The current piece followed by the next queue.
+    pub queue: Vec<u32>,
+    pub hold: Option<u32>,
+    /// Solver options that change which solutions exist.
+    pub options: String,
+}
+
+impl CacheKey {
+    pub fn new(board: &Board, config: &SolverConfig) -> CacheKey {
+        CacheKey {
+            board: board.get_bitboard().0,
+            queue: board
+                .current_piece
+                .into_iter()
+                .chain(board.next_pieces.iter().copied())
+                .collect(),
+            hold: board.hold,
+            options: format!(
+                "{:?} {:?} {:?}",
+                config.hold, config.placeability, config.max_pieces
+            ),
+        }
+    }
+}
+
+/// Least recently used cache of solutions.
+pub struct SolutionCache {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<CacheKey, (Vec<Step>, u64)>,
+}
+
+impl SolutionCache {
+    pub fn new(capacity: usize) -> SolutionCache {
+        SolutionCache {
+            capacity,
+            tick: 0,
+            entries: HashMap::new(),
+        }
+    }
+
+    pub fn get(&mut self, key: &CacheKey) -> Option<Vec<Step>> {
+        self.tick += 1;
+        let tick = self.tick;
+        self.entries.get_mut(key).map(|entry| {
+            entry.1 = tick;
+            entry.0.clone()
+        })
+    }
+
+    pub fn insert(&mut self, key: CacheKey, steps: Vec<Step>) {
+        self.tick += 1;
+        self.entries.insert(key, (steps, self.tick));
+        while self.entries.len() > self.capacity {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, tick))| *tick)
+                .map(|(key, _)| key.clone())
+                .unwrap();
+            self.entries.remove(&oldest);
+        }
+    }
+
+    /// Loads a cache written by `save`. A missing or unreadable file gives an empty cache.
+    pub fn load(path: impl AsRef<Path>, capacity: usize) -> SolutionCache {
+        let mut cache = SolutionCache::new(capacity);
+        let entries: Vec<(CacheKey, Vec<Step>)> = std::fs::read(path)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default();
+        for (key, steps) in entries {
+            cache.insert(key, steps);
+        }
+        cache
+    }
+
+    /// Writes the entries from least to most recently used.
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let mut entries: Vec<_> = self.entries.iter().collect();
+        entries.sort_by_key(|(_, (_, tick))| *tick);
+        let entries: Vec<_> = entries
+            .into_iter()
+            .map(|(key, (steps, _))| (key, steps))
+            .collect();
+        std::fs::write(path, serde_json::to_vec(&entries)?)
+    }
+}
+
+/// The rest of `plan` after its first step was placed as `m`, moved down by the rows `m`
+/// cleared.
+pub fn remainder(plan: &[Step], m: &Move) -> Vec<Step> {
+    plan.iter()
+        .skip(1)
+        .map(|step| step.after_clear(m.cleared_rows))
+        .collect()
+}
+
+/// Whether the first step of `steps` can be played on `board` right now.
+pub fn fits(board: &Board, steps: &[Step]) -> bool {
+    let step = match steps.first() {
+        Some(step) => step,
+        None => return false,
+    };
+    let next = board.hold.or_else(|| board.next_pieces.first().copied());
+    let field = Field::from_board(board);
+    (board.current_piece == Some(step.piece) || next == Some(step.piece))
+        && step.cells.iter().all(|&(x, y)| !field.occupied(x, y))
+}
@@ -0,0 +1,293 @@
+extern crate serde_json;
+
+use crate::hotkeys::{Bindings, Hotkeys};
+use crate::layout::FieldPlacement;
+use crate::theme::Theme;
+use serde::{Deserialize, Serialize};
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+
+pub const CONFIG_PATH: &str = "pc_assist.json";
+
+/// Which placements the solver may use.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Placeability {
+    /// Only placements reachable by moving sideways and hard dropping.
+    HardDrop,
+    /// `pcf::placeability::simple_srs_spins`.
+    SimpleSrs,
+    /// Anything reachable from spawn with SRS rotations, soft drops and tucks.
+    Srs,
+}
+
+impl Placeability {
+    fn name(self) -> &'static str {
+        match self {
+            Placeability::HardDrop => "hard-drop",
+            Placeability::SimpleSrs => "simple-srs",
+            Placeability::Srs => "srs",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SolverConfig {
+    pub hold: bool,
+    pub placeability: Placeability,
+    /// Number of pieces the solver may place, `None` for the whole known queue.
+    pub max_pieces: Option<usize>,
+    /// Wall-clock budget for one search in milliseconds, `None` to search until done.
+    pub timeout_ms: Option<u64>,
+    /// Worker threads one search is split over.
+    pub threads: usize,
+    /// Number of solutions to cycle through with the next solution hotkey. Only the first is
+    /// searched for with a new board, the rest when the hotkey is first pressed.
+    pub alternatives: usize,
+}
+
+impl Default for SolverConfig {
+    fn default() -> Self {
+        SolverConfig {
+            hold: true,
+            placeability: Placeability::SimpleSrs,
+            max_pieces: None,
+            timeout_ms: Some(5000),
+            threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            alternatives: 3,
+        }
+    }
+}
+
+impl SolverConfig {
+    /// One line description for the HUD.
+    pub fn summary(&self) -> String {
+        let mut parts = vec![
+            if self.hold { "hold" } else { "no hold" }.to_string(),
+            self.placeability.name().to_string(),
+        ];
+        if let Some(n) = self.max_pieces {
+            parts.push(format!("{} pieces", n));
+        }
+        if let Some(ms) = self.timeout_ms {
+            parts.push(format!("{}ms", ms));
+        }
+        if self.threads > 1 {
+            parts.push(format!("{} threads", self.threads));
+        }
+        parts.join(", ")
+    }
+}
+
+/// How the assistant shows its plans.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WindowMode {
+    /// A transparent window over the game.
+    #[default]
+    Overlay,
+    /// A normal window that draws the board, hold and queue as well, for a second monitor
+    /// or when the game runs fullscreen.
+    Practice,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    /// Number of solutions kept in memory.
+    pub capacity: usize,
+    /// File the cache is loaded from at startup and saved to on exit.
+    pub path: Option<String>,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            capacity: 4096,
+            path: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub solver: SolverConfig,
+    pub cache: CacheConfig,
+    /// Opening book consulted on empty boards. It only holds PCs made from the current
+    /// piece and the 5 previews, see `book`.
+    pub book: Option<String>,
+    /// Generate an opening book to this path instead of running the assistant.
+    #[serde(skip)]
+    pub build_book: Option<String>,
+    /// Where the overlay draws the field, set by calibrating the overlay.
+    pub field: FieldPlacement,
+    pub window: WindowMode,
+    pub hotkeys: Bindings,
+    /// How the plan is drawn.
+    pub theme: Theme,
+    /// Play in the built-in simulator with this seed instead of tracking the game.
+    #[serde(skip)]
+    pub simulate: Option<u64>,
+    /// File the config was read from, which calibration writes back to.
+    #[serde(skip)]
+    pub path: String,
+}
+
+fn invalid(message: String) -> Error {
+    Error::new(ErrorKind::InvalidInput, message)
+}
+
+impl Config {
+    /// Reads a JSON config file. A missing file gives the default config.
+    pub fn load(path: impl AsRef<Path>) -> Result<Config> {
+        match std::fs::read(path) {
+            Ok(data) => Ok(serde_json::from_slice(&data)?),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Writes `field` to the config file at `path`, leaving everything else in it as it
+    /// is, so options that were never written keep following the defaults.
+    pub fn save_field(path: impl AsRef<Path>, field: FieldPlacement) -> Result<()> {
+        let path = path.as_ref();
+        let mut config = match std::fs::read(path) {
+            Ok(data) => serde_json::from_slice(&data)?,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                serde_json::Value::Object(Default::default())
+            }
+            Err(e) => return Err(e),
+        };
+        match config.as_object_mut() {
+            Some(object) => object.insert("field".to_string(), serde_json::to_value(field)?),
+            None => return Err(invalid(format!("{} is not a JSON object", path.display()))),
+        };
+        std::fs::write(path, serde_json::to_vec_pretty(&config)?)
+    }
+
+    /// Loads the config file (`--config <path>`, `pc_assist.json` by default) and applies
+    /// the remaining command line options on top of it.
+    pub fn from_args(args: impl Iterator<Item = String>) -> Result<Config> {
+        let args: Vec<String> = args.collect();
+        let path = args
+            .iter()
+            .position(|a| a == "--config")
+            .map(|i| {
+                args.get(i + 1)
+                    .cloned()
+                    .ok_or_else(|| invalid("--config needs a path".to_string()))
+            })
+            .transpose()?
+            .unwrap_or_else(|| CONFIG_PATH.to_string());
+        let mut config = Config::load(&path)?;
+        config.path = path;
+        Hotkeys::new(&config.hotkeys).map_err(invalid)?;
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| invalid(format!("{} needs a value", arg)))
+            };
+            match arg.as_str() {
+                "--config" => {
+                    value()?;
+                }
+                "--hold" => config.solver.hold = true,
+                "--no-hold" => config.solver.hold = false,
+                "--placeability" => {
+                    config.solver.placeability = match value()?.as_str() {
+                        "hard-drop" => Placeability::HardDrop,
+                        "simple-srs" => Placeability::SimpleSrs,
+                        "srs" => Placeability::Srs,
+                        v => return Err(invalid(format!("unknown placeability {:?}", v))),
+                    }
+                }
+                "--max-pieces" => {
+                    let v = value()?;
+                    config.solver.max_pieces = Some(
+                        v.parse()
+                            .map_err(|_| invalid(format!("invalid piece count {:?}", v)))?,
+                    )
+                }
+                "--no-timeout" => config.solver.timeout_ms = None,
+                "--timeout" => {
+                    let v = value()?;
+                    config.solver.timeout_ms = Some(
+                        v.parse()
+                            .map_err(|_| invalid(format!("invalid timeout {:?}", v)))?,
+                    )
+                }
+                "--threads" => {
+                    let v = value()?;
+                    config.solver.threads = v
+                        .parse()
+                        .ok()
+                        .filter(|&n| n > 0)
+                        .ok_or_else(|| invalid(format!("invalid thread count {:?}", v)))?
+                }
+                "--window" => {
+                    config.window = match value()?.as_str() {
+                        "overlay" => WindowMode::Overlay,
+                        "practice" => WindowMode::Practice,
+                        v => return Err(invalid(format!("unknown window mode {:?}", v))),
+                    }
+                }
+                "--simulate" => {
+                    let v = value()?;
+                    config.simulate = Some(
+                        v.parse()
+                            .map_err(|_| invalid(format!("invalid seed {:?}", v)))?,
+                    );
+                    config.window = WindowMode::Practice;
+                }
+                "--cache-file" => config.cache.path = Some(value()?),
+                "--book" => config.book = Some(value()?),
+                "--build-book" => config.build_book = Some(value()?),
+                _ => return Err(invalid(format!("unknown option {:?}", arg))),
+            }
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saving_the_field_keeps_the_rest_of_the_file() {
+        let dir = std::env::temp_dir().join(format!("pc_assist_config_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("pc_assist.json");
+        std::fs::write(&path, r#"{"solver": {"hold": false}, "book": "book.bin"}"#).unwrap();
+
+        let field = FieldPlacement {
+            origin: (3.5, 4.25),
+            cell: 0.75,
+        };
+        Config::save_field(&path, field).unwrap();
+        let saved: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        // Options that were not in the file, like the machine's thread count, stay out.
+        assert_eq!(
+            saved,
+            serde_json::from_str::<serde_json::Value>(
+                r#"{"solver": {"hold": false}, "book": "book.bin",
+                    "field": {"origin": [3.5, 4.25], "cell": 0.75}}"#
+            )
+            .unwrap()
+        );
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config.field, field);
+        assert!(!config.solver.hold);
+
+        std::fs::remove_file(&path).unwrap();
+        Config::save_field(&path, field).unwrap();
+        assert_eq!(Config::load(&path).unwrap().field, field);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
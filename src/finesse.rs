@@ -0,0 +1,235 @@
+extern crate pcf;
+
+use crate::board::Board;
+use crate::plan::Step;
+use crate::srs::{rotation_index, FallingPiece, Field};
+use pcf::Piece;
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Input {
+    Hold,
+    Left,
+    Right,
+    DasLeft,
+    DasRight,
+    Cw,
+    Ccw,
+    Rotate180,
+    SoftDrop,
+    HardDrop,
+}
+
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Input::Hold => "Hold",
+            Input::Left => "L",
+            Input::Right => "R",
+            Input::DasLeft => "DAS-L",
+            Input::DasRight => "DAS-R",
+            Input::Cw => "CW",
+            Input::Ccw => "CCW",
+            Input::Rotate180 => "180",
+            Input::SoftDrop => "SD",
+            Input::HardDrop => "HD",
+        })
+    }
+}
+
+// Taps come before DAS so that equally short sequences prefer them.
+const MOVES: [Input; 8] = [
+    Input::Left,
+    Input::Right,
+    Input::Cw,
+    Input::Ccw,
+    Input::Rotate180,
+    Input::DasLeft,
+    Input::DasRight,
+    Input::SoftDrop,
+];
+
+/// Moves `piece` by one input, `None` if it cannot move. Hold and hard drop never move it.
+pub fn apply(piece: &FallingPiece, field: &Field, input: Input) -> Option<FallingPiece> {
+    match input {
+        Input::Left => piece.shift(field, -1),
+        Input::Right => piece.shift(field, 1),
+        Input::DasLeft => piece.das(field, -1),
+        Input::DasRight => piece.das(field, 1),
+        Input::Cw => piece.rotate(field, 1),
+        Input::Ccw => piece.rotate(field, 3),
+        Input::Rotate180 => piece.rotate(field, 2),
+        Input::SoftDrop => {
+            let dropped = piece.sonic_drop(field);
+            if dropped.y != piece.y {
+                Some(dropped)
+            } else {
+                None
+            }
+        }
+        Input::Hold | Input::HardDrop => None,
+    }
+}
+
+/// Finds the shortest input sequence that takes `piece` from its spawn position to lock
+/// on `target`. Soft drop always drops to the floor, and the sequence ends with a hard
+/// drop.
+pub fn find_inputs(
+    field: &Field,
+    piece: Piece,
+    target: [(i32, i32); 4],
+    allow_180: bool,
+) -> Option<Vec<Input>> {
+    let mut target = target;
+    target.sort();
+
+    let spawn = FallingPiece::spawn(piece);
+    if !spawn.fits(field) {
+        return None;
+    }
+
+    let key = |p: &FallingPiece| (rotation_index(p.rotation), p.x, p.y);
+    let mut visited = HashSet::new();
+    visited.insert(key(&spawn));
+    let mut queue = VecDeque::new();
+    queue.push_back((spawn, vec![]));
+
+    while let Some((current, path)) = queue.pop_front() {
+        let mut cells = current.sonic_drop(field).cells();
+        cells.sort();
+        if cells == target {
+            let mut path = path;
+            path.push(Input::HardDrop);
+            return Some(path);
+        }
+
+        for &input in &MOVES {
+            if input == Input::Rotate180 && !allow_180 {
+                continue;
+            }
+            if let Some(next) = apply(&current, field, input) {
+                if visited.insert(key(&next)) {
+                    let mut path = path.clone();
+                    path.push(input);
+                    queue.push_back((next, path));
+                }
+            }
+        }
+    }
+    None
+}
+
+/// The cells of every position `piece` can lock on from its spawn position, each sorted.
+/// One search answers `find_inputs` for every target on the same field.
+pub fn reachable(field: &Field, piece: Piece, allow_180: bool) -> HashSet<[(i32, i32); 4]> {
+    let mut locks = HashSet::new();
+    let spawn = FallingPiece::spawn(piece);
+    if !spawn.fits(field) {
+        return locks;
+    }
+
+    let key = |p: &FallingPiece| (rotation_index(p.rotation), p.x, p.y);
+    let mut visited = HashSet::new();
+    visited.insert(key(&spawn));
+    let mut queue = VecDeque::new();
+    queue.push_back(spawn);
+
+    while let Some(current) = queue.pop_front() {
+        let mut cells = current.sonic_drop(field).cells();
+        cells.sort();
+        locks.insert(cells);
+
+        for &input in &MOVES {
+            if input == Input::Rotate180 && !allow_180 {
+                continue;
+            }
+            if let Some(next) = apply(&current, field, input) {
+                if visited.insert(key(&next)) {
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+    locks
+}
+
+/// Inputs for the first step of a solution on `board`, including a hold when the step
+/// isn't for the current piece.
+pub fn step_inputs(board: &Board, step: &Step) -> Option<Vec<Input>> {
+    let piece = pcf::PIECES[step.piece as usize];
+    // PPT2 has no 180 rotation.
+    let mut inputs = find_inputs(&Field::from_board(board), piece, step.cells, false)?;
+    if board.current_piece != Some(step.piece) {
+        inputs.insert(0, Input::Hold);
+    }
+    Some(inputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Input::*;
+
+    fn inputs(piece: Piece, target: [(i32, i32); 4]) -> Option<Vec<Input>> {
+        find_inputs(&Field::empty(), piece, target, false)
+    }
+
+    #[test]
+    fn pieces_dropped_at_spawn_need_only_a_hard_drop() {
+        assert_eq!(
+            inputs(Piece::T, [(3, 0), (4, 0), (5, 0), (4, 1)]),
+            Some(vec![HardDrop])
+        );
+        assert_eq!(
+            inputs(Piece::I, [(3, 0), (4, 0), (5, 0), (6, 0)]),
+            Some(vec![HardDrop])
+        );
+    }
+
+    #[test]
+    fn flat_pieces_at_the_walls_use_das() {
+        assert_eq!(
+            inputs(Piece::I, [(0, 0), (1, 0), (2, 0), (3, 0)]),
+            Some(vec![DasLeft, HardDrop])
+        );
+        assert_eq!(
+            inputs(Piece::I, [(6, 0), (7, 0), (8, 0), (9, 0)]),
+            Some(vec![DasRight, HardDrop])
+        );
+        assert_eq!(
+            inputs(Piece::O, [(0, 0), (1, 0), (0, 1), (1, 1)]),
+            Some(vec![DasLeft, HardDrop])
+        );
+    }
+
+    #[test]
+    fn vertical_i_at_the_walls_rotates_then_uses_das() {
+        assert_eq!(
+            inputs(Piece::I, [(0, 0), (0, 1), (0, 2), (0, 3)]),
+            Some(vec![Cw, DasLeft, HardDrop])
+        );
+        assert_eq!(
+            inputs(Piece::I, [(9, 0), (9, 1), (9, 2), (9, 3)]),
+            Some(vec![Cw, DasRight, HardDrop])
+        );
+    }
+
+    #[test]
+    fn one_column_away_uses_a_tap() {
+        assert_eq!(
+            inputs(Piece::T, [(2, 0), (3, 0), (4, 0), (3, 1)]),
+            Some(vec![Left, HardDrop])
+        );
+    }
+
+    #[test]
+    fn unreachable_targets_have_no_inputs() {
+        let mut field = Field::empty();
+        field.rows[1] = 0b11_1111_1111;
+        assert_eq!(
+            find_inputs(&field, Piece::T, [(3, 0), (4, 0), (5, 0), (4, 1)], false),
+            None
+        );
+    }
+}
@@ -0,0 +1,106 @@
+use crate::board::{Board, BoardEvent};
+use crate::config::SolverConfig;
+use crate::finesse::{self, Input};
+use crate::plan::Step;
+use crate::sim::Simulator;
+use crate::solver::{self, SolverStatus};
+use crate::tracker::Tracker;
+
+/// How the harness picks placements.
+pub enum Script {
+    /// Play the first step of each suggestion, and hard drop at spawn without one.
+    FollowSuggestions,
+    /// Play these inputs for each piece in turn. Pieces without inputs are hard dropped.
+    Inputs(Vec<Vec<Input>>),
+}
+
+/// What happened in one harness game.
+#[derive(Clone, Debug, Default)]
+pub struct Report {
+    pub pieces: usize,
+    /// Pieces placed when a PC was first suggested.
+    pub first_suggestion: Option<usize>,
+    pub perfect_clears: usize,
+    /// Pieces placed when a board's queue differed from the pieces the simulator dealt.
+    pub queue_errors: Vec<usize>,
+}
+
+/// The first snapshot of a game, which shows the queue before the first piece spawns.
+fn game_start(sim: &Simulator) -> Board {
+    Board {
+        current_piece: None,
+        next_pieces: sim.upcoming(sim.board.next_pieces.len()),
+        ..sim.board.clone()
+    }
+}
+
+fn check_queue(sim: &Simulator, board: &Board, report: &mut Report) {
+    let dealt = sim.upcoming(1 + board.next_pieces.len());
+    if board.current_piece != dealt.first().copied() || board.next_pieces[..] != dealt[1..] {
+        report.queue_errors.push(report.pieces);
+    }
+}
+
+/// Plays a game of `sim` for up to `max_pieces` pieces, passing every snapshot through the
+/// same `Tracker` as the game and solving each board with `config`.
+pub fn play(
+    mut sim: Simulator,
+    max_pieces: usize,
+    script: &Script,
+    config: &SolverConfig,
+) -> Report {
+    let mut tracker = Tracker::new(false);
+    let mut report = Report::default();
+    let mut plan: Option<Vec<Step>> = None;
+
+    tracker.observe(game_start(&sim));
+    let mut snapshot = Some(sim.board.clone());
+    while report.pieces < max_pieces && !sim.topped_out {
+        for event in snapshot
+            .take()
+            .map_or(vec![], |b| tracker.observe(b).events)
+        {
+            if let BoardEvent::Continue(board) = event {
+                check_queue(&sim, &board, &mut report);
+                let (status, placements) = solver::solve(&board, config, |_| {});
+                if status == SolverStatus::Found && report.first_suggestion.is_none() {
+                    report.first_suggestion = Some(report.pieces);
+                }
+                plan = placements.map(|s| s.iter().map(Step::from_placement).collect());
+            }
+        }
+
+        let mut inputs = match script {
+            Script::FollowSuggestions => plan
+                .as_ref()
+                .and_then(|steps| finesse::step_inputs(&sim.board, &steps[0]))
+                .unwrap_or_default(),
+            Script::Inputs(pieces) => pieces.get(report.pieces).cloned().unwrap_or_default(),
+        };
+        if inputs.last() != Some(&Input::HardDrop) {
+            inputs.push(Input::HardDrop);
+        }
+
+        for input in inputs {
+            let hold = sim.board.hold;
+            let locked = sim.input(input);
+            if locked.is_some() {
+                report.pieces += 1;
+                if sim.board.height() == 0 {
+                    report.perfect_clears += 1;
+                }
+                snapshot = Some(sim.board.clone());
+                break;
+            }
+            // The game shows a hold as its own snapshot.
+            if sim.board.hold != hold {
+                for event in tracker.observe(sim.board.clone()).events {
+                    if let BoardEvent::Continue(board) = event {
+                        check_queue(&sim, &board, &mut report);
+                    }
+                }
+            }
+        }
+    }
+    report
+}
@@ -0,0 +1,146 @@
+extern crate pcf;
+
+use crate::board::{Board, BoardDiff, HEIGHT, WIDTH};
+use crate::srs::{FallingPiece, ROTATIONS};
+use pcf::Rotation;
+
+/// A locked piece, positioned like a `srs::FallingPiece`.
+#[derive(Clone, Copy, Debug)]
+pub struct Move {
+    pub piece: u32,
+    pub rotation: Rotation,
+    pub x: i32,
+    pub y: i32,
+    pub hold: bool,
+    /// Bit `y` is set when row `y` was cleared by this piece.
+    pub cleared_rows: u64,
+}
+
+impl Move {
+    pub fn lines_cleared(&self) -> u32 {
+        self.cleared_rows.count_ones()
+    }
+
+    pub fn cells(&self) -> [(i32, i32); 4] {
+        FallingPiece {
+            piece: pcf::PIECES[self.piece as usize],
+            rotation: self.rotation,
+            x: self.x,
+            y: self.y,
+        }
+        .cells()
+    }
+}
+
+pub struct History {
+    pub moves: Vec<Move>,
+    hold_pending: bool,
+}
+
+impl History {
+    pub fn new() -> History {
+        History {
+            moves: vec![],
+            hold_pending: false,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.moves.clear();
+        self.hold_pending = false;
+    }
+
+    /// Records the move that turned `prev` into `now`, if a piece was locked.
+    ///
+    /// A hold shows up as its own snapshot before the lock, so it is remembered and
+    /// attached to the next locked piece.
+    pub fn update(&mut self, prev: &Board, now: &Board, diff: &BoardDiff) -> Option<Move> {
+        if !diff.piece_locked {
+            self.hold_pending |= diff.hold_used;
+            return None;
+        }
+
+        let piece = if diff.hold_used {
+            prev.hold.or_else(|| prev.next_pieces.first().copied())
+        } else {
+            prev.current_piece
+        }?;
+        let hold = self.hold_pending || diff.hold_used;
+        self.hold_pending = false;
+
+        // Garbage shifts the whole field, so the placement can't be matched cell by cell.
+        if diff.garbage_received > 0 {
+            return None;
+        }
+
+        let mut m = infer_move(prev, now, piece)?;
+        m.hold = hold;
+        self.moves.push(m);
+        Some(m)
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        History::new()
+    }
+}
+
+fn occupied(board: &Board) -> Vec<[bool; WIDTH]> {
+    (0..HEIGHT)
+        .map(|y| {
+            let mut row = [false; WIDTH];
+            for (x, cell) in row.iter_mut().enumerate() {
+                *cell = board.columns.get(x).is_some_and(|c| c[y] != -1);
+            }
+            row
+        })
+        .collect()
+}
+
+/// Finds a placement of `piece` on `prev` that, after clearing lines, gives `now`.
+pub fn infer_move(prev: &Board, now: &Board, piece: u32) -> Option<Move> {
+    let before = occupied(prev);
+    let after = occupied(now);
+    let top = (prev.height() + 4).min(HEIGHT) as i32;
+
+    for &rotation in &ROTATIONS {
+        for y in -2..top {
+            for x in -2..WIDTH as i32 + 2 {
+                let m = Move {
+                    piece,
+                    rotation,
+                    x,
+                    y,
+                    hold: false,
+                    cleared_rows: 0,
+                };
+                let mut rows = before.clone();
+                let fits = m.cells().iter().all(|&(cx, cy)| {
+                    let free = cx >= 0
+                        && cx < WIDTH as i32
+                        && cy >= 0
+                        && cy < HEIGHT as i32
+                        && !rows[cy as usize][cx as usize];
+                    if free {
+                        rows[cy as usize][cx as usize] = true;
+                    }
+                    free
+                });
+                if !fits {
+                    continue;
+                }
+
+                let cleared_rows = (0..HEIGHT)
+                    .filter(|&y| rows[y].iter().all(|&c| c))
+                    .fold(0, |mask, y| mask | 1 << y);
+                rows.retain(|row| row.iter().any(|&c| !c));
+                rows.resize(HEIGHT, [false; WIDTH]);
+                if rows == after {
+                    return Some(Move { cleared_rows, ..m });
+                }
+            }
+        }
+    }
+    None
+}
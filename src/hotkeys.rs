@@ -0,0 +1,222 @@
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Windows virtual-key codes of the modifier keys.
+const VK_SHIFT: u8 = 0x10;
+const VK_CONTROL: u8 = 0x11;
+const VK_MENU: u8 = 0x12;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+    /// Hide or show everything the overlay draws.
+    ToggleOverlay,
+    /// Show the next of the solutions found for the current board.
+    NextSolution,
+    /// Switch between showing the next placement and the whole plan.
+    ToggleFullPath,
+    /// Stop or restart solving new boards.
+    PauseSolver,
+    /// Start calibrating where the overlay draws the field.
+    Calibrate,
+}
+
+/// Which key combination triggers each action, like `"F5"` or `"Ctrl+Shift+P"`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Bindings {
+    pub toggle_overlay: String,
+    pub next_solution: String,
+    pub toggle_full_path: String,
+    pub pause_solver: String,
+    pub calibrate: String,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Bindings {
+            toggle_overlay: "F5".to_string(),
+            next_solution: "F6".to_string(),
+            toggle_full_path: "F7".to_string(),
+            pause_solver: "F8".to_string(),
+            calibrate: "F2".to_string(),
+        }
+    }
+}
+
+/// A key with the modifiers that must be held with it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyCombo {
+    /// Windows virtual-key code.
+    pub key: u8,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+fn key_code(name: &str) -> Option<u8> {
+    let upper = name.to_ascii_uppercase();
+    let bytes = upper.as_bytes();
+    if bytes.len() == 1 && (bytes[0].is_ascii_uppercase() || bytes[0].is_ascii_digit()) {
+        return Some(bytes[0]);
+    }
+    if let Some(n) = upper.strip_prefix('F').and_then(|n| n.parse::<u8>().ok()) {
+        if (1..=24).contains(&n) {
+            return Some(0x6F + n);
+        }
+    }
+    Some(match upper.as_str() {
+        "SPACE" => 0x20,
+        "PAGEUP" => 0x21,
+        "PAGEDOWN" => 0x22,
+        "END" => 0x23,
+        "HOME" => 0x24,
+        "INSERT" => 0x2D,
+        "DELETE" => 0x2E,
+        "PAUSE" => 0x13,
+        _ => return None,
+    })
+}
+
+impl FromStr for KeyCombo {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<KeyCombo, String> {
+        let mut combo = KeyCombo {
+            key: 0,
+            ctrl: false,
+            shift: false,
+            alt: false,
+        };
+        let mut parts: Vec<&str> = s.split('+').map(str::trim).collect();
+        let key = parts.pop().unwrap_or_default();
+        for modifier in parts {
+            match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" => combo.ctrl = true,
+                "shift" => combo.shift = true,
+                "alt" => combo.alt = true,
+                _ => return Err(format!("unknown modifier {:?} in {:?}", modifier, s)),
+            }
+        }
+        combo.key = key_code(key).ok_or_else(|| format!("unknown key {:?} in {:?}", key, s))?;
+        Ok(combo)
+    }
+}
+
+/// Turns key states into actions, independent of how the states are read so it works the
+/// same for a polled keyboard and for recorded key states.
+pub struct Hotkeys {
+    bindings: Vec<(KeyCombo, Action)>,
+    /// Whether each binding's combination was down at the last poll.
+    held: Vec<bool>,
+}
+
+impl Hotkeys {
+    pub fn new(bindings: &Bindings) -> Result<Hotkeys, String> {
+        let bindings = vec![
+            (bindings.toggle_overlay.parse()?, Action::ToggleOverlay),
+            (bindings.next_solution.parse()?, Action::NextSolution),
+            (bindings.toggle_full_path.parse()?, Action::ToggleFullPath),
+            (bindings.pause_solver.parse()?, Action::PauseSolver),
+            (bindings.calibrate.parse()?, Action::Calibrate),
+        ];
+        Ok(Hotkeys {
+            held: vec![false; bindings.len()],
+            bindings,
+        })
+    }
+
+    /// The actions whose combinations were pressed since the last poll. `is_down` tells
+    /// whether the key with a virtual-key code is held. Modifiers must match exactly, so
+    /// `F5` does not fire for `Ctrl+F5`.
+    pub fn poll(&mut self, is_down: impl Fn(u8) -> bool) -> Vec<Action> {
+        let ctrl = is_down(VK_CONTROL);
+        let shift = is_down(VK_SHIFT);
+        let alt = is_down(VK_MENU);
+        let mut actions = vec![];
+        for ((combo, action), held) in self.bindings.iter().zip(&mut self.held) {
+            let down = is_down(combo.key)
+                && combo.ctrl == ctrl
+                && combo.shift == shift
+                && combo.alt == alt;
+            if down && !*held {
+                actions.push(*action);
+            }
+            *held = down;
+        }
+        actions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VK_F5: u8 = 0x74;
+
+    fn combo(key: u8, ctrl: bool, shift: bool, alt: bool) -> KeyCombo {
+        KeyCombo {
+            key,
+            ctrl,
+            shift,
+            alt,
+        }
+    }
+
+    #[test]
+    fn parses_keys_with_modifiers() {
+        assert_eq!("Ctrl+Shift+P".parse(), Ok(combo(b'P', true, true, false)));
+        assert_eq!("alt + 7".parse(), Ok(combo(b'7', false, false, true)));
+        assert_eq!("F24".parse(), Ok(combo(0x87, false, false, false)));
+        assert_eq!("f1".parse(), Ok(combo(0x70, false, false, false)));
+        assert_eq!("Shift+PageUp".parse(), Ok(combo(0x21, false, true, false)));
+    }
+
+    #[test]
+    fn rejects_unknown_modifiers_and_keys() {
+        assert!("Super+P"
+            .parse::<KeyCombo>()
+            .unwrap_err()
+            .contains("modifier"));
+        for bad in &["F25", "F0", "Ctrl+", "", "PP", "Ctrl+Esc"] {
+            let err = bad.parse::<KeyCombo>().unwrap_err();
+            assert!(err.contains("unknown key"), "{:?}: {}", bad, err);
+        }
+        let bindings = Bindings {
+            pause_solver: "Hyper+F8".to_string(),
+            ..Bindings::default()
+        };
+        assert!(Hotkeys::new(&bindings).is_err());
+    }
+
+    #[test]
+    fn actions_fire_once_per_press() {
+        let mut hotkeys = Hotkeys::new(&Bindings::default()).unwrap();
+        let f5 = |key| key == VK_F5;
+        assert_eq!(hotkeys.poll(|_| false), vec![]);
+        assert_eq!(hotkeys.poll(f5), vec![Action::ToggleOverlay]);
+        assert_eq!(hotkeys.poll(f5), vec![]);
+        assert_eq!(hotkeys.poll(|_| false), vec![]);
+        assert_eq!(hotkeys.poll(f5), vec![Action::ToggleOverlay]);
+    }
+
+    #[test]
+    fn modifiers_must_match_exactly() {
+        let bindings = Bindings {
+            next_solution: "Ctrl+F5".to_string(),
+            ..Bindings::default()
+        };
+        let mut hotkeys = Hotkeys::new(&bindings).unwrap();
+        let ctrl_f5 = |key| key == VK_F5 || key == VK_CONTROL;
+        let ctrl_shift_f5 = |key| key == VK_F5 || key == VK_CONTROL || key == VK_SHIFT;
+
+        assert_eq!(hotkeys.poll(ctrl_f5), vec![Action::NextSolution]);
+        // Adding Shift releases Ctrl+F5 without pressing plain F5.
+        assert_eq!(hotkeys.poll(ctrl_shift_f5), vec![]);
+        assert_eq!(
+            hotkeys.poll(|key| key == VK_F5),
+            vec![Action::ToggleOverlay]
+        );
+        assert_eq!(hotkeys.poll(ctrl_f5), vec![Action::NextSolution]);
+    }
+}
@@ -0,0 +1,309 @@
+use crate::board::WIDTH;
+use serde::{Deserialize, Serialize};
+
+/// Size of the game's picture in field cells. The game draws a 16:9 picture and fills the
+/// rest of a wider or taller client area with bars.
+pub const VIEW_WIDTH: f32 = 53.5;
+pub const VIEW_HEIGHT: f32 = 30.05;
+
+/// Horizontal centre of the player 1 field in the game's picture, in cells. The overlay
+/// centres its messages on it.
+pub const FIELD_CENTER_X: f32 = 14.1;
+/// Height of the player 1 field's bottom edge above the bottom of the picture, in cells.
+pub const FIELD_BOTTOM: f32 = 6.1;
+
+/// A rectangle in client pixels, measured from the top left corner with y going down.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+/// Where a field is drawn within the game's picture, in cells of the picture.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FieldPlacement {
+    /// Bottom left corner of field cell (0, 0), with y going up.
+    pub origin: (f32, f32),
+    /// Size of one field cell.
+    pub cell: f32,
+}
+
+impl FieldPlacement {
+    /// The left field, which is the only one in single player modes. The game draws its
+    /// cells the size of a picture cell.
+    pub const PLAYER_1: FieldPlacement = FieldPlacement {
+        origin: (FIELD_CENTER_X - WIDTH as f32 / 2.0, FIELD_BOTTOM),
+        cell: 1.0,
+    };
+
+    /// The right field in two player modes, which mirrors the left one.
+    pub const PLAYER_2: FieldPlacement = FieldPlacement {
+        origin: (
+            VIEW_WIDTH - FIELD_CENTER_X - WIDTH as f32 / 2.0,
+            FIELD_BOTTOM,
+        ),
+        cell: 1.0,
+    };
+}
+
+/// Which fields the game's picture shows. The picture keeps its shape at every aspect
+/// ratio, so the fields only move with the bars `Layout` adds around it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameMode {
+    Solo,
+    Versus,
+}
+
+impl GameMode {
+    /// Where each player's field is drawn, player 1 first.
+    pub fn fields(self) -> &'static [FieldPlacement] {
+        match self {
+            GameMode::Solo => &[FieldPlacement::PLAYER_1],
+            GameMode::Versus => &[FieldPlacement::PLAYER_1, FieldPlacement::PLAYER_2],
+        }
+    }
+}
+
+impl FieldPlacement {
+    /// Picture position of the bottom left corner of field cell `(x, y)`.
+    pub fn cell_point(&self, x: i32, y: i32) -> (f32, f32) {
+        (
+            self.origin.0 + x as f32 * self.cell,
+            self.origin.1 + y as f32 * self.cell,
+        )
+    }
+}
+
+impl Default for FieldPlacement {
+    fn default() -> Self {
+        FieldPlacement::PLAYER_1
+    }
+}
+
+/// Maps picture cells to pixels for one client area size.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Layout {
+    pub client_width: f32,
+    pub client_height: f32,
+    /// Where the game's picture is in the client area.
+    pub view: Rect,
+}
+
+impl Layout {
+    pub fn new(client_width: f32, client_height: f32) -> Layout {
+        let scale = (client_width / VIEW_WIDTH).min(client_height / VIEW_HEIGHT);
+        let (width, height) = (VIEW_WIDTH * scale, VIEW_HEIGHT * scale);
+        Layout {
+            client_width,
+            client_height,
+            view: Rect {
+                x: (client_width - width) / 2.0,
+                y: (client_height - height) / 2.0,
+                width,
+                height,
+            },
+        }
+    }
+
+    pub fn pixels_per_cell(&self) -> f32 {
+        self.view.width / VIEW_WIDTH
+    }
+
+    /// Left, right, bottom and top of the client area in picture cells, for an
+    /// orthographic projection with the picture at `0..VIEW_WIDTH` and `0..VIEW_HEIGHT`.
+    pub fn projection(&self) -> (f32, f32, f32, f32) {
+        let ppc = self.pixels_per_cell();
+        let left = -self.view.x / ppc;
+        let bottom = -(self.client_height - self.view.y - self.view.height) / ppc;
+        (
+            left,
+            left + self.client_width / ppc,
+            bottom,
+            bottom + self.client_height / ppc,
+        )
+    }
+
+    /// Client pixels covered by the picture area `width` by `height` cells with its bottom
+    /// left corner at `(x, y)`.
+    pub fn picture_rect(&self, x: f32, y: f32, width: f32, height: f32) -> Rect {
+        let ppc = self.pixels_per_cell();
+        Rect {
+            x: self.view.x + x * ppc,
+            y: self.view.y + self.view.height - (y + height) * ppc,
+            width: width * ppc,
+            height: height * ppc,
+        }
+    }
+
+    /// Client pixels of the picture point `(x, y)`, measured from the top left corner.
+    pub fn picture_point(&self, x: f32, y: f32) -> (f32, f32) {
+        let rect = self.picture_rect(x, y, 0.0, 0.0);
+        (rect.x, rect.y)
+    }
+
+    /// Client pixels covered by field cell `(x, y)`.
+    pub fn cell_rect(&self, field: &FieldPlacement, x: i32, y: i32) -> Rect {
+        let (px, py) = field.cell_point(x, y);
+        self.picture_rect(px, py, field.cell, field.cell)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-3 * a.abs().max(b.abs()).max(1.0)
+    }
+
+    fn assert_rect(a: Rect, b: Rect) {
+        assert!(
+            close(a.x, b.x)
+                && close(a.y, b.y)
+                && close(a.width, b.width)
+                && close(a.height, b.height),
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn sixteen_by_nine_fills_the_client_area() {
+        for &(w, h) in &[(1280.0, 720.0), (1920.0, 1080.0), (2560.0, 1440.0)] {
+            let layout = Layout::new(w, h);
+            assert!(close(layout.view.x, 0.0), "{}x{}", w, h);
+            assert!(close(layout.view.width, w));
+            // The picture is not exactly 16:9, so a sliver of bar is left above and below.
+            assert!(layout.view.y >= 0.0 && layout.view.y < 0.01 * h);
+        }
+    }
+
+    #[test]
+    fn field_scales_with_the_resolution() {
+        let field = FieldPlacement::PLAYER_1;
+        let small = Layout::new(1280.0, 720.0);
+        let large = Layout::new(1920.0, 1080.0);
+        for &(x, y) in &[(0, 0), (9, 0), (0, 19), (9, 19)] {
+            let a = small.cell_rect(&field, x, y);
+            let b = large.cell_rect(&field, x, y);
+            assert_rect(
+                Rect {
+                    x: a.x * 1.5,
+                    y: a.y * 1.5,
+                    width: a.width * 1.5,
+                    height: a.height * 1.5,
+                },
+                b,
+            );
+        }
+    }
+
+    #[test]
+    fn wide_and_tall_clients_get_centred_bars() {
+        // Ultrawide: bars left and right, the field moves right by the bar's width.
+        let wide = Layout::new(3440.0, 1440.0);
+        let normal = Layout::new(VIEW_WIDTH / VIEW_HEIGHT * 1440.0, 1440.0);
+        assert!(close(wide.view.height, normal.view.height));
+        assert!(close(wide.view.x * 2.0 + wide.view.width, 3440.0));
+        let field = FieldPlacement::PLAYER_1;
+        let (a, b) = (wide.cell_rect(&field, 3, 7), normal.cell_rect(&field, 3, 7));
+        assert!(close(a.x - b.x, wide.view.x - normal.view.x));
+        assert!(close(a.y, b.y));
+
+        // 4:3 and a small window: bars above and below.
+        for &(w, h) in &[(1024.0, 768.0), (800.0, 600.0)] {
+            let tall = Layout::new(w, h);
+            assert!(close(tall.view.x, 0.0));
+            assert!(close(tall.view.y * 2.0 + tall.view.height, h));
+            assert!(close(
+                tall.view.width / tall.view.height,
+                VIEW_WIDTH / VIEW_HEIGHT
+            ));
+        }
+    }
+
+    #[test]
+    fn projection_and_rects_agree() {
+        for &(w, h) in &[(1920.0, 1080.0), (3440.0, 1440.0), (1024.0, 768.0)] {
+            let layout = Layout::new(w, h);
+            assert_rect(
+                layout.picture_rect(0.0, 0.0, VIEW_WIDTH, VIEW_HEIGHT),
+                layout.view,
+            );
+
+            // The projection's edges are the client area's edges.
+            let (left, right, bottom, top) = layout.projection();
+            assert_rect(
+                layout.picture_rect(left, bottom, right - left, top - bottom),
+                Rect {
+                    x: 0.0,
+                    y: 0.0,
+                    width: w,
+                    height: h,
+                },
+            );
+        }
+    }
+
+    #[test]
+    fn player_2_mirrors_player_1_at_every_aspect_ratio() {
+        let sizes = [
+            (1280.0, 720.0),
+            (1920.0, 1080.0),
+            (2560.0, 1440.0),
+            (3440.0, 1440.0),
+            (1024.0, 768.0),
+            (800.0, 600.0),
+        ];
+        let (p1, p2) = (FieldPlacement::PLAYER_1, FieldPlacement::PLAYER_2);
+        for &(w, h) in &sizes {
+            let layout = Layout::new(w, h);
+            for &(x, y) in &[(0, 0), (3, 7), (9, 19)] {
+                let (a, b) = (layout.cell_rect(&p1, x, y), layout.cell_rect(&p2, 9 - x, y));
+                // Mirrored about the middle of the client area, bars included.
+                assert!(close(a.x + b.x + b.width, w), "{}x{} {:?} {:?}", w, h, a, b);
+                assert!(close(a.y, b.y));
+                assert!(close(a.width, b.width));
+            }
+        }
+    }
+
+    #[test]
+    fn game_modes_list_player_1_first() {
+        assert_eq!(GameMode::Solo.fields(), &[FieldPlacement::PLAYER_1]);
+        assert_eq!(
+            GameMode::Versus.fields(),
+            &[FieldPlacement::PLAYER_1, FieldPlacement::PLAYER_2]
+        );
+
+        // Every field of every mode fits in the picture.
+        for &mode in &[GameMode::Solo, GameMode::Versus] {
+            for field in mode.fields() {
+                let (left, bottom) = field.cell_point(0, 0);
+                let (right, top) = field.cell_point(10, 20);
+                assert!(left > 0.0 && right < VIEW_WIDTH, "{:?}", mode);
+                assert!(bottom > 0.0 && top < VIEW_HEIGHT, "{:?}", mode);
+            }
+        }
+    }
+
+    #[test]
+    fn player_1_field_is_inside_the_picture() {
+        let field = FieldPlacement::PLAYER_1;
+        let (left, bottom) = field.cell_point(0, 0);
+        let (right, top) = field.cell_point(10, 20);
+        assert!(close((left + right) / 2.0, FIELD_CENTER_X));
+        assert!(close(bottom, FIELD_BOTTOM));
+        assert!(left > 0.0 && right < VIEW_WIDTH / 2.0);
+        assert!(bottom > 0.0 && top < VIEW_HEIGHT);
+
+        // Cell rects are measured from the top, so higher rows are nearer y = 0.
+        let layout = Layout::new(1920.0, 1080.0);
+        let low = layout.cell_rect(&field, 0, 0);
+        let high = layout.cell_rect(&field, 0, 19);
+        assert!(close(low.y - high.y, 19.0 * layout.pixels_per_cell()));
+    }
+}
@@ -0,0 +1,24 @@
+//! Board tracking, solving and plan bookkeeping, independent of the game process and the
+//! overlay window so benchmarks and tools can use them.
+
+pub mod adherence;
+pub mod board;
+pub mod book;
+pub mod cache;
+pub mod config;
+pub mod finesse;
+pub mod harness;
+pub mod history;
+pub mod hotkeys;
+pub mod layout;
+pub mod overlay;
+pub mod plan;
+pub mod render;
+pub mod sim;
+pub mod solver;
+pub mod srs;
+pub mod stats;
+#[cfg(test)]
+mod test_util;
+pub mod theme;
+pub mod tracker;
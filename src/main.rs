@@ -1,7 +1,20 @@
 extern crate pcf;
+#[cfg(windows)]
 extern crate process_memory;
-use crate::plan::{Cells, PlanPlacement};
-use board::{Board, BoardEvent};
+use pc_assist::board::{Board, BoardEvent};
+use pc_assist::book::Book;
+use pc_assist::cache::{CacheKey, SolutionCache};
+use pc_assist::config::{Config, WindowMode};
+use pc_assist::finesse::Input;
+use pc_assist::overlay::OverlayEvent;
+use pc_assist::plan::Step;
+use pc_assist::sim::Simulator;
+use pc_assist::solver::SolverStatus;
+use pc_assist::stats::StatsLog;
+#[cfg(windows)]
+use pc_assist::tracker::Tracker;
+use pc_assist::{cache, finesse, solver};
+#[cfg(windows)]
 use ppt::Ppt;
 use std::sync::mpsc::{channel, Receiver, Sender};
 use std::sync::Arc;
@@ -9,14 +22,33 @@ use std::sync::Arc;
 #[cfg(windows)]
 extern crate winapi;
 
-mod board;
-mod plan;
+#[cfg(windows)]
 mod ppt;
 mod window;
 
-#[cfg(not(windows))]
-fn main() {
-    println!("pc_assist can only be run on Windows.")
+const STATS_PATH: &str = "pc_assist_stats.json";
+
+/// Hotkeys the window forwards because they change what gets solved.
+pub enum Control {
+    NextSolution,
+    PauseSolver,
+}
+
+/// What the solver loop waits for: boards from the game or simulator, and hotkeys.
+enum Event {
+    Board(BoardEvent),
+    Control(Control),
+}
+
+/// Passes everything from `recv` on to `send` as an `Event`.
+fn forward<T: Send + 'static>(recv: Receiver<T>, send: Sender<Event>, event: fn(T) -> Event) {
+    std::thread::spawn(move || {
+        for t in recv {
+            if send.send(event(t)).is_err() {
+                break;
+            }
+        }
+    });
 }
 
 /// A helper function to get a Pid from the name of a process
@@ -64,168 +96,355 @@ pub fn get_pid(process_name: &str) -> process_memory::Pid {
     0
 }
 
+#[cfg(windows)]
 fn run(send: Sender<BoardEvent>, ppt_pid: process_memory::Pid) {
     use process_memory::*;
-    use std::collections::HashSet;
-    use std::iter::FromIterator;
 
     let process_handler: ProcessHandle = ppt_pid.try_into_process_handle().unwrap();
     let ppt = Ppt {
         process_handle: process_handler,
     };
 
-    let mut board = Board {
-        columns: vec![],
-        current_piece: None,
-        hold: None,
-        next_pieces: vec![],
-    };
-    let mut mino_set = HashSet::<u32>::new();
-    let mut preview_next_pieces: Vec<u32> = vec![];
-    let mut _put_count = 0;
-
     let mut player_index = ppt.find_player_index().unwrap();
-
-    let mut can_prediction = ppt.get_current_piece(player_index).is_none();
-    let mut need_reset = can_prediction;
-    let mut resetted = !need_reset;
+    let mut tracker = Tracker::new(ppt.get_current_piece(player_index).is_some());
 
     while ppt.still_active().unwrap() {
-        let current_piece = ppt.get_current_piece(player_index);
-        if current_piece.is_none() && !need_reset && !resetted {
-            can_prediction = true;
-            need_reset = true;
-        }
-        if current_piece.is_none() && need_reset && !resetted {
-            let next_pieces = match ppt.get_next_pieces(player_index) {
-                Ok(i) => i,
-                Err(_e) => continue,
-            };
-            mino_set = HashSet::<_>::from_iter(
-                HashSet::<_>::from_iter(vec![0, 1, 2, 3, 4, 5, 6].into_iter())
-                    .difference(&HashSet::<_>::from_iter(next_pieces.clone().into_iter()))
-                    .into_iter()
-                    .copied(),
-            );
-            _put_count = 0;
-            preview_next_pieces = next_pieces.clone();
-            resetted = true;
-            need_reset = false;
-            player_index = ppt.find_player_index().unwrap();
-        }
-        if mino_set.is_empty() {
-            mino_set = HashSet::<_>::from_iter(vec![0, 1, 2, 3, 4, 5, 6].into_iter());
-        }
-        if current_piece == board.current_piece {
-            continue;
-        }
-
-        resetted = false;
-        board.current_piece = current_piece;
-
-        println!("current_piece: {:?}", current_piece);
-
-        board.columns = match ppt.get_columns(player_index) {
-            Ok(v) => v,
+        let board = match ppt.get_board(player_index) {
+            Ok(b) => b,
             Err(e) => {
                 println!("e: {:?}", e);
                 continue;
             }
         };
-
-        let mut next_pieces = ppt.get_next_pieces(player_index).unwrap();
-        if can_prediction {
-            if next_pieces != preview_next_pieces {
-                _put_count += 1;
-                if let Some(i) = next_pieces.last() {
-                    mino_set.retain(|&j| j != *i);
-                }
-            }
-            preview_next_pieces = preview_next_pieces;
-
-            if mino_set.len() == 1 {
-                for i in mino_set.iter() {
-                    next_pieces.push(*i);
-                }
-            }
+        let update = tracker.observe(board);
+        if update.reset {
+            player_index = ppt.find_player_index().unwrap();
+        }
+        for event in update.events {
+            send.send(event).ok();
         }
-        board.next_pieces = next_pieces.clone();
-        board.hold = ppt.get_hold(player_index).ok();
-
-        println!("sent!");
-        send.send(BoardEvent::Continue(board.clone())).ok();
     }
 
     println!("PPT closed");
     send.send(BoardEvent::Exit).ok();
 }
 
-fn run_window(recv: Receiver<Arc<Option<Cells>>>, ppt_pid: process_memory::Pid) {
+/// Plays the simulator with the keys sent from the window, reporting its boards like `run`
+/// reports the game's. Topping out starts a new game with the next seed.
+fn run_simulator(
+    send: Sender<BoardEvent>,
+    window_send: Sender<OverlayEvent>,
+    inputs: Receiver<Input>,
+    seed: u64,
+) {
+    let mut seed = seed;
+    let mut sim = Simulator::new(seed);
+    send.send(BoardEvent::Continue(sim.board.clone())).ok();
+    window_send
+        .send(OverlayEvent::Falling(sim.falling_step()))
+        .ok();
+
+    for input in inputs {
+        let hold = sim.board.hold;
+        let locked = sim.input(input);
+        if let Some(m) = locked {
+            send.send(BoardEvent::Lock(m)).ok();
+        }
+        if sim.topped_out {
+            seed = seed.wrapping_add(1);
+            sim = Simulator::new(seed);
+        }
+        if locked.is_some() || sim.board.hold != hold {
+            send.send(BoardEvent::Continue(sim.board.clone())).ok();
+        }
+        window_send
+            .send(OverlayEvent::Falling(sim.falling_step()))
+            .ok();
+    }
+    send.send(BoardEvent::Exit).ok();
+}
+
+/// Runs the overlay window, over the window of the process `game_pid` or in a window of its
+/// own for practice.
+fn run_window(
+    recv: Receiver<OverlayEvent>,
+    game_pid: Option<u32>,
+    config: Config,
+    inputs: Option<Sender<Input>>,
+    controls: Sender<Control>,
+) {
     use game_util::prelude::*;
 
-    let mut events = glutin::EventsLoop::new();
-    let (context, lsize) = game_util::create_context(
-        glutin::WindowBuilder::new()
+    let builder = match config.window {
+        WindowMode::Overlay => glutin::WindowBuilder::new()
             .with_transparency(true)
-            .with_always_on_top(true)
-            .with_resizable(true),
-        0,
-        true,
-        &mut events,
-    );
-
-    let mut game = window::Game::new(context, lsize, recv, ppt_pid);
+            .with_always_on_top(true),
+        WindowMode::Practice => glutin::WindowBuilder::new()
+            .with_title("pc_assist")
+            .with_dimensions(glutin::dpi::LogicalSize::new(1280.0, 720.0)),
+    };
+    let mut events = glutin::EventsLoop::new();
+    let (context, lsize) =
+        game_util::create_context(builder.with_resizable(true), 0, true, &mut events);
+
+    let mut game = window::Game::new(context, lsize, recv, game_pid, &config, inputs, controls);
     game_util::gameloop(&mut events, &mut game, 60.0, true);
     println!("window closed");
 }
 
 include!(concat!(env!("OUT_DIR"), "/sprites.rs"));
 
-#[cfg(windows)]
+/// Shows `steps` as the plan for `board`.
+fn show_plan(window_send: &Sender<OverlayEvent>, board: &Board, steps: &[Step]) {
+    println!("PC: {:?}", steps);
+    window_send
+        .send(OverlayEvent::Plan(Arc::new(Some(steps[0]))))
+        .unwrap();
+    window_send
+        .send(OverlayEvent::Path(steps.to_vec()))
+        .unwrap();
+    let inputs = finesse::step_inputs(board, &steps[0]);
+    println!("inputs: {:?}", inputs);
+    window_send
+        .send(OverlayEvent::Inputs(inputs.unwrap_or_default()))
+        .unwrap();
+}
+
+/// Reports the nodes searched so far to the window.
+fn progress(window_send: &Sender<OverlayEvent>) -> impl FnMut(u64) + Send + 'static {
+    let window_send = window_send.clone();
+    move |nodes| {
+        window_send
+            .send(OverlayEvent::Status(SolverStatus::Searching { nodes }))
+            .ok();
+    }
+}
+
 fn main() -> std::io::Result<()> {
     use std::thread;
 
-    let ppt_pid = get_pid("PuyoPuyoTetris2.exe");
+    let config = Config::from_args(std::env::args().skip(1))?;
+    println!("solver: {}", config.solver.summary());
 
-    let mut prev_soln: Vec<pcf::Placement> = vec![];
+    if let Some(path) = &config.build_book {
+        let book = Book::build(&config.solver, |done, total| {
+            println!("book: {}/{}", done, total);
+        });
+        book.save(path)?;
+        return Ok(());
+    }
+    let book = match &config.book {
+        Some(path) => match Book::load(path, &config.solver) {
+            Ok(book) => Some(book),
+            Err(e) => {
+                println!("book: {}", e);
+                None
+            }
+        },
+        None => None,
+    };
+
+    let mut suggested: Option<Vec<Step>> = None;
+    // Solutions for the last board, cycled through with the next solution hotkey.
+    // Only the first is searched for with a new board; the rest when the hotkey is pressed.
+    let mut alternatives: Vec<Vec<Step>> = vec![];
+    let mut alternative = 0;
+    let mut searched_alternatives = false;
+    let mut last_board: Option<Board> = None;
+    // Pausing skips searching new boards; a search already running still finishes.
+    let mut paused = false;
+    let mut remainder: Option<Vec<Step>> = None;
+    let mut stats = StatsLog::open(STATS_PATH)?;
+    let mut solution_cache = match &config.cache.path {
+        Some(path) => SolutionCache::load(path, config.cache.capacity),
+        None => SolutionCache::new(config.cache.capacity),
+    };
 
     let (window_send, window_recv) = channel();
     let (board_send, board_recv) = channel();
+    let (controls, control_recv) = channel();
+    let (event_send, event_recv) = channel();
+    let resolve = event_send.clone();
+    forward(board_recv, event_send.clone(), Event::Board);
+    forward(control_recv, event_send, Event::Control);
+
+    let window_config = config.clone();
+    if let Some(seed) = config.simulate {
+        let (input_send, input_recv) = channel();
+        let sim_window_send = window_send.clone();
+        thread::spawn(move || {
+            run_window(window_recv, None, window_config, Some(input_send), controls)
+        });
+        thread::spawn(move || run_simulator(board_send, sim_window_send, input_recv, seed));
+    } else {
+        #[cfg(windows)]
+        {
+            let ppt_pid = get_pid("PuyoPuyoTetris2.exe");
+            thread::spawn(move || {
+                run_window(window_recv, Some(ppt_pid), window_config, None, controls)
+            });
+            thread::spawn(move || run(board_send, ppt_pid));
+        }
+        #[cfg(not(windows))]
+        {
+            return Err(std::io::Error::other(
+                "following Puyo Puyo Tetris 2 needs Windows; practice with --simulate <seed>",
+            ));
+        }
+    }
 
-    thread::spawn(move || run_window(window_recv, ppt_pid));
-    thread::spawn(move || run(board_send, ppt_pid));
+    window_send
+        .send(OverlayEvent::Solver(config.solver.clone()))
+        .unwrap();
 
     let mut count = 0;
 
     loop {
-        match board_recv.recv().unwrap() {
-            BoardEvent::Continue(board) => {
+        match event_recv.recv().unwrap() {
+            Event::Board(BoardEvent::Continue(board)) => {
                 count += 1;
                 println!("UPDATE {}", count);
                 let s = Arc::new(None);
-                window_send.send(Arc::clone(&s)).unwrap();
-                pcf::solve_pc(
-                    &board.get_queue(),
-                    board.get_bitboard(),
-                    true,
-                    true,
-                    pcf::placeability::simple_srs_spins,
-                    |soln| {
-                        let soln_vec = soln.clone().to_vec();
-                        if soln_vec != prev_soln {
-                            prev_soln = soln_vec;
-                            println!("PC: {:?}", soln);
-                            let s = Arc::new(Some(soln[0].cells().clone()));
-                            window_send.send(Arc::clone(&s)).unwrap();
+                window_send
+                    .send(OverlayEvent::Plan(Arc::clone(&s)))
+                    .unwrap();
+                window_send
+                    .send(OverlayEvent::Board(board.clone()))
+                    .unwrap();
+
+                if stats.session.record_board(&board) {
+                    println!("PC!");
+                    if let Err(e) = stats.save() {
+                        println!("e: {:?}", e);
+                    }
+                }
+
+                let key = CacheKey::new(&board, &config.solver);
+                let mut status = SolverStatus::Found;
+                alternatives = if let Some(steps) =
+                    remainder.take().filter(|steps| cache::fits(&board, steps))
+                {
+                    println!("following plan");
+                    solution_cache.insert(key, steps.clone());
+                    vec![steps]
+                } else if let Some(steps) = book.as_ref().and_then(|b| b.lookup(&board)) {
+                    println!("book");
+                    vec![steps]
+                } else if let Some(steps) = solution_cache.get(&key) {
+                    stats.session.record_cache(true);
+                    vec![steps]
+                } else if paused {
+                    status = SolverStatus::Paused;
+                    vec![]
+                } else {
+                    stats.session.record_cache(false);
+                    window_send
+                        .send(OverlayEvent::Status(SolverStatus::Searching { nodes: 0 }))
+                        .unwrap();
+
+                    let start = std::time::Instant::now();
+                    let (result, placements) =
+                        solver::solve(&board, &config.solver, progress(&window_send));
+                    status = result;
+                    stats
+                        .session
+                        .record_solve(start.elapsed(), status == SolverStatus::Found);
+                    let steps: Option<Vec<Step>> =
+                        placements.map(|s| s.iter().map(Step::from_placement).collect());
+                    if let Some(steps) = &steps {
+                        solution_cache.insert(key, steps.clone());
+                    }
+                    steps.into_iter().collect()
+                };
+
+                alternative = 0;
+                searched_alternatives = false;
+                if let Some(steps) = alternatives.first() {
+                    show_plan(&window_send, &board, steps);
+                }
+                suggested = alternatives.first().cloned();
+                last_board = Some(board);
+
+                println!("solver: {:?}", status);
+                window_send.send(OverlayEvent::Status(status)).unwrap();
+                window_send
+                    .send(OverlayEvent::Stats(stats.session.clone()))
+                    .unwrap();
+            }
+            Event::Board(BoardEvent::Lock(m)) => {
+                stats.session.record_lock(&m);
+                alternatives.clear();
+                if let Some(plan) = &suggested {
+                    let adherence = &mut stats.session.adherence;
+                    if adherence.record(&plan[0].cells(), &m) {
+                        remainder = Some(cache::remainder(plan, &m));
+                    } else {
+                        println!("plan broken");
+                        // The board for this lock is sent right after and gets solved again
+                        // instead of continuing the old plan.
+                        remainder = None;
+                        window_send.send(OverlayEvent::PlanBroken).unwrap();
+                    }
+                    if let Some(p) = adherence.percentage() {
+                        println!("adherence: {:.1}%", p);
+                    }
+                }
+            }
+            Event::Control(Control::NextSolution) => {
+                let board = match (&last_board, alternatives.first()) {
+                    (Some(board), Some(_)) => board,
+                    _ => continue,
+                };
+                if !searched_alternatives {
+                    searched_alternatives = true;
+                    let (_, placements) = solver::solve_alternatives(
+                        board,
+                        &config.solver,
+                        config.solver.alternatives,
+                        progress(&window_send),
+                    );
+                    // The shown solution may have come from the book or the cache, so it
+                    // stays first and is not repeated.
+                    for steps in placements {
+                        let steps: Vec<Step> = steps.iter().map(Step::from_placement).collect();
+                        if !alternatives.contains(&steps) {
+                            alternatives.push(steps);
                         }
-                        pcf::SearchStatus::Abort
-                    },
-                );
+                    }
+                    alternatives.truncate(config.solver.alternatives.max(1));
+                    window_send
+                        .send(OverlayEvent::Status(SolverStatus::Found))
+                        .unwrap();
+                }
+                if alternatives.len() > 1 {
+                    alternative = (alternative + 1) % alternatives.len();
+                    println!("solution {}/{}", alternative + 1, alternatives.len());
+                    show_plan(&window_send, board, &alternatives[alternative]);
+                    suggested = Some(alternatives[alternative].clone());
+                }
+            }
+            Event::Control(Control::PauseSolver) => {
+                paused = !paused;
+                println!("solver {}", if paused { "paused" } else { "resumed" });
+                if suggested.is_none() {
+                    if paused {
+                        window_send
+                            .send(OverlayEvent::Status(SolverStatus::Paused))
+                            .unwrap();
+                    } else if let Some(board) = last_board.clone() {
+                        // Solve the board that was skipped while paused.
+                        resolve.send(Event::Board(BoardEvent::Continue(board))).ok();
+                    }
+                }
             }
-            BoardEvent::Exit => break,
+            Event::Board(BoardEvent::Exit) => break,
         }
     }
 
+    stats.save()?;
+    if let Some(path) = &config.cache.path {
+        solution_cache.save(path)?;
+    }
     println!("close");
     Ok(())
 }
@@ -0,0 +1,422 @@
+use crate::board::Board;
+use crate::config::SolverConfig;
+use crate::finesse::Input;
+use crate::hotkeys::Action;
+use crate::layout::{FieldPlacement, FIELD_CENTER_X};
+use crate::plan::Step;
+use crate::solver::SolverStatus;
+use crate::stats::Stats;
+use crate::theme::{PlanStyle, Theme};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// How long the "plan broken" warning stays on screen.
+const PLAN_BROKEN_DURATION: f64 = 1.5;
+
+/// Colours of the PPT cell codes 0 to 8: the pieces S Z J L T O I, garbage and ghost.
+pub const CELL_COLORS: [[u8; 4]; 9] = [
+    [89, 177, 1, 255],
+    [215, 15, 55, 255],
+    [33, 65, 198, 255],
+    [227, 91, 2, 255],
+    [175, 41, 138, 255],
+    [227, 159, 2, 255],
+    [15, 155, 215, 255],
+    [120, 120, 120, 255],
+    [255, 255, 255, 64],
+];
+
+/// Width of the hold and next piece sprites in field cells.
+const PIECE_SPRITE_CELLS: f32 = 4.0;
+
+/// Rows of the field the game shows.
+const VISIBLE_ROWS: usize = 20;
+
+pub enum OverlayEvent {
+    Plan(Arc<Option<Step>>),
+    /// Every placement of the plan, the first one included.
+    Path(Vec<Step>),
+    PlanBroken,
+    Stats(Stats),
+    Inputs(Vec<Input>),
+    Solver(SolverConfig),
+    Status(SolverStatus),
+    /// The tracked board, drawn when the overlay shows the board itself.
+    Board(Board),
+    /// The simulator's falling piece.
+    Falling(Option<Step>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sprite {
+    /// `plan.N.png`, indexed by the bits of a cell's directions.
+    Plan(usize),
+    /// `piece.N.png`, indexed by `pcf::Piece`.
+    Piece(usize),
+    /// `fill.png`, a solid cell under the outline of filled plans.
+    Fill,
+}
+
+impl Sprite {
+    /// Size of one field cell in the sprite image.
+    pub fn pixels_per_cell(self) -> f32 {
+        match self {
+            Sprite::Plan(_) | Sprite::Fill => 83.0,
+            Sprite::Piece(_) => 41.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/// Something the overlay can be drawn on. Positions are in cells of the game's picture
+/// (see `layout`), with y going up.
+pub trait OverlayRenderer {
+    /// Draws `sprite` at `(x, y)`, scaled so one of its cells is `cell` picture cells.
+    fn sprite(&mut self, sprite: Sprite, x: f32, y: f32, cell: f32, tint: [u8; 4]);
+    fn text(&mut self, text: &str, x: f32, y: f32, align: Align, color: [u8; 4], size: f32);
+}
+
+/// What the overlay shows, updated from `OverlayEvent`s.
+pub struct Overlay {
+    pub plan: Arc<Option<Step>>,
+    path: Vec<Step>,
+    plan_broken_at: Option<Instant>,
+    hud: Vec<String>,
+    inputs: String,
+    solver: String,
+    status: Option<SolverStatus>,
+    board: Option<Board>,
+    falling: Option<Step>,
+    /// Draw the board, hold and queue too, for a window that is not over the game.
+    pub show_board: bool,
+    pub field: FieldPlacement,
+    /// The field placement before calibration started, while calibrating.
+    pub calibration: Option<FieldPlacement>,
+    /// Hidden overlays draw nothing but the calibration grid.
+    pub visible: bool,
+    /// Draw the whole plan instead of only its next placement.
+    pub full_path: bool,
+    pub theme: Theme,
+}
+
+impl Overlay {
+    pub fn new(field: FieldPlacement) -> Overlay {
+        Overlay {
+            plan: Arc::new(None),
+            path: vec![],
+            plan_broken_at: None,
+            hud: vec![],
+            inputs: String::new(),
+            solver: String::new(),
+            status: None,
+            board: None,
+            falling: None,
+            show_board: false,
+            field,
+            calibration: None,
+            visible: true,
+            full_path: false,
+            theme: Theme::default(),
+        }
+    }
+
+    /// Applies the hotkey actions that only change what is drawn. Returns false for the
+    /// ones it leaves to the window and the solver.
+    pub fn hotkey(&mut self, action: Action) -> bool {
+        match action {
+            Action::ToggleOverlay => self.visible = !self.visible,
+            Action::ToggleFullPath => self.full_path = !self.full_path,
+            Action::NextSolution | Action::PauseSolver | Action::Calibrate => return false,
+        }
+        true
+    }
+
+    pub fn handle(&mut self, event: OverlayEvent) {
+        match event {
+            OverlayEvent::Plan(s) => self.plan = s,
+            OverlayEvent::Path(path) => self.path = path,
+            OverlayEvent::PlanBroken => self.plan_broken_at = Some(Instant::now()),
+            OverlayEvent::Stats(stats) => self.hud = stats.summary(),
+            OverlayEvent::Solver(config) => self.solver = config.summary(),
+            OverlayEvent::Status(status) => self.status = Some(status),
+            OverlayEvent::Board(board) => self.board = Some(board),
+            OverlayEvent::Falling(step) => self.falling = step,
+            OverlayEvent::Inputs(inputs) => {
+                self.inputs = inputs
+                    .iter()
+                    .map(Input::to_string)
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            }
+        }
+    }
+
+    fn draw_board(&self, r: &mut impl OverlayRenderer, board: &Board) {
+        let cell = self.field.cell;
+        for (x, column) in board.columns.iter().enumerate() {
+            for (y, &code) in column.iter().enumerate().take(VISIBLE_ROWS) {
+                if let Some(&color) = CELL_COLORS.get(code as usize) {
+                    let (px, py) = self.field.cell_point(x as i32, y as i32);
+                    r.sprite(Sprite::Fill, px, py, cell, color);
+                }
+            }
+        }
+
+        if let Some(step) = self.falling {
+            for &(x, y) in &step.cells {
+                let (px, py) = self.field.cell_point(x, y);
+                r.sprite(Sprite::Fill, px, py, cell, CELL_COLORS[step.piece as usize]);
+            }
+        }
+
+        let mut piece = |i: u32, x: f32, y: i32| {
+            let sprite = Sprite::Piece(pcf::PIECES[i as usize] as usize);
+            let (px, py) = self.field.cell_point(0, y);
+            r.sprite(sprite, px + x * cell, py, cell, CELL_COLORS[i as usize]);
+        };
+        if let Some(hold) = board.hold {
+            piece(hold, -PIECE_SPRITE_CELLS - 0.5, VISIBLE_ROWS as i32 - 2);
+        }
+        for (i, &next) in board.next_pieces.iter().take(5).enumerate() {
+            piece(next, 10.5, VISIBLE_ROWS as i32 - 2 - 3 * i as i32);
+        }
+    }
+
+    fn draw_calibration_grid(&self, r: &mut impl OverlayRenderer) {
+        for y in 0..20 {
+            for x in 0..10 {
+                let (px, py) = self.field.cell_point(x, y);
+                r.sprite(
+                    Sprite::Plan(0),
+                    px,
+                    py,
+                    self.field.cell,
+                    [255, 255, 255, 96],
+                );
+            }
+        }
+        r.text(
+            "drag to move, scroll to resize, Enter to save, Esc to cancel",
+            FIELD_CENTER_X,
+            28.5,
+            Align::Center,
+            [255; 4],
+            0.6,
+        );
+    }
+
+    /// Draws one placement of the plan, numbered with `number` if the theme numbers them.
+    fn draw_step(&self, r: &mut impl OverlayRenderer, step: &Step, next: bool, number: usize) {
+        let cell = self.field.cell;
+        let tint = self.theme.tint(step.piece, next);
+        for &(x, y, d) in &step.cells() {
+            let (px, py) = self.field.cell_point(x, y);
+            if self.theme.style == PlanStyle::Filled {
+                r.sprite(Sprite::Fill, px, py, cell, self.theme.fill(tint));
+            }
+            r.sprite(Sprite::Plan(d.to_bits() as usize), px, py, cell, tint);
+        }
+        if self.theme.step_numbers && self.full_path {
+            let (sx, sy) = step
+                .cells
+                .iter()
+                .fold((0, 0), |(sx, sy), &(x, y)| (sx + x, sy + y));
+            let (px, py) = self.field.cell_point(0, 0);
+            r.text(
+                &number.to_string(),
+                px + (sx as f32 / 4.0 + 0.5) * cell,
+                py + (sy as f32 / 4.0 + 0.25) * cell,
+                Align::Center,
+                [255; 4],
+                0.6 * cell,
+            );
+        }
+    }
+
+    pub fn draw(&mut self, r: &mut impl OverlayRenderer) {
+        if let (true, true, Some(board)) = (self.visible, self.show_board, &self.board) {
+            self.draw_board(r, board);
+        }
+        if self.calibration.is_some() {
+            self.draw_calibration_grid(r);
+        }
+        if !self.visible {
+            return;
+        }
+
+        if self.full_path && self.plan.is_some() {
+            for (i, step) in self.path.iter().enumerate().skip(1) {
+                self.draw_step(r, step, false, i + 1);
+            }
+        }
+        if let Some(step) = *self.plan {
+            self.draw_step(r, &step, true, 1);
+        }
+
+        if let Some(at) = self.plan_broken_at {
+            let elapsed = at.elapsed().as_secs_f64();
+            if elapsed > PLAN_BROKEN_DURATION {
+                self.plan_broken_at = None;
+            } else if ((elapsed * 4.0) as u32).is_multiple_of(2) {
+                r.text(
+                    "plan broken",
+                    FIELD_CENTER_X,
+                    26.5,
+                    Align::Center,
+                    [255, 64, 64, 255],
+                    1.0,
+                );
+            }
+        }
+
+        let status = match self.status {
+            None => String::new(),
+            Some(SolverStatus::Searching { nodes }) => format!("searching... {}k", nodes / 1000),
+            Some(SolverStatus::Found) => self.inputs.clone(),
+            Some(SolverStatus::NoPc) => "no PC".to_string(),
+            Some(SolverStatus::TimedOut) => "timed out".to_string(),
+            Some(SolverStatus::Paused) => "solver paused".to_string(),
+        };
+        r.text(&status, FIELD_CENTER_X, 27.5, Align::Center, [255; 4], 0.7);
+
+        for (i, line) in std::iter::once(&self.solver)
+            .chain(self.hud.iter())
+            .enumerate()
+        {
+            r.text(
+                line,
+                8.6,
+                25.5 - i as f32 * 0.8,
+                Align::Right,
+                [255; 4],
+                0.6,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{bit_position, BITBOARD_ROWS, WIDTH};
+    use crate::test_util;
+
+    /// Records where plan and fill sprites are drawn, as field cells.
+    struct Recorder {
+        field: FieldPlacement,
+        cells: Vec<(i32, i32)>,
+        fills: Vec<((i32, i32), [u8; 4])>,
+    }
+
+    impl OverlayRenderer for Recorder {
+        fn sprite(&mut self, sprite: Sprite, x: f32, y: f32, cell: f32, tint: [u8; 4]) {
+            if let Sprite::Piece(_) = sprite {
+                return;
+            }
+            assert_eq!(cell, self.field.cell);
+            let fx = (x - self.field.origin.0) / self.field.cell;
+            let fy = (y - self.field.origin.1) / self.field.cell;
+            assert!((fx - fx.round()).abs() < 1e-4 && (fy - fy.round()).abs() < 1e-4);
+            let position = (fx.round() as i32, fy.round() as i32);
+            match sprite {
+                Sprite::Fill => self.fills.push((position, tint)),
+                _ => self.cells.push(position),
+            }
+        }
+
+        fn text(&mut self, _: &str, _: f32, _: f32, _: Align, _: [u8; 4], _: f32) {}
+    }
+
+    fn drawn_cells(field: FieldPlacement, step: Step) -> Vec<(i32, i32)> {
+        let mut overlay = Overlay::new(field);
+        overlay.handle(OverlayEvent::Plan(Arc::new(Some(step))));
+        let mut recorder = Recorder {
+            field,
+            cells: vec![],
+            fills: vec![],
+        };
+        overlay.draw(&mut recorder);
+        recorder.cells.sort();
+        recorder.cells
+    }
+
+    #[test]
+    fn drawn_cells_are_the_cells_a_placement_fills() {
+        let boards = [
+            "",
+            "LL........\nL.........\nL.........\n",
+            "......ZZ..\nJJJ..ZZ...\n",
+            "IIII......\n",
+        ];
+        let fields = [
+            FieldPlacement::PLAYER_1,
+            FieldPlacement {
+                origin: (2.25, 3.5),
+                cell: 0.75,
+            },
+        ];
+        for text in &boards {
+            let board: Board = text.parse().unwrap();
+            let before = board.get_bitboard();
+            for &piece in &pcf::PIECES {
+                for placement in test_util::placements(before, &[piece]) {
+                    let after = before.0 | placement.board().0;
+                    let mut filled: Vec<_> = (0..(WIDTH * BITBOARD_ROWS) as u32)
+                        .filter(|&bit| (after & !before.0) & 1 << bit != 0)
+                        .map(bit_position)
+                        .collect();
+                    filled.sort();
+                    for &field in &fields {
+                        let step = Step::from_placement(&placement);
+                        assert_eq!(
+                            drawn_cells(field, step),
+                            filled,
+                            "{:?} on {:?}",
+                            placement,
+                            text
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn board_cells_are_filled_with_their_piece_colours() {
+        let field = FieldPlacement::PLAYER_1;
+        let mut overlay = Overlay::new(field);
+        overlay.show_board = true;
+        overlay.handle(OverlayEvent::Board(
+            "LL........\nLJJJ....ZZ\n".parse().unwrap(),
+        ));
+        let mut recorder = Recorder {
+            field,
+            cells: vec![],
+            fills: vec![],
+        };
+        overlay.draw(&mut recorder);
+
+        let (l, j, z) = (CELL_COLORS[3], CELL_COLORS[2], CELL_COLORS[1]);
+        recorder.fills.sort_by_key(|&(position, _)| position);
+        assert_eq!(
+            recorder.fills,
+            vec![
+                ((0, 0), l),
+                ((0, 1), l),
+                ((1, 0), j),
+                ((1, 1), l),
+                ((2, 0), j),
+                ((3, 0), j),
+                ((8, 0), z),
+                ((9, 0), z),
+            ]
+        );
+        assert_eq!(recorder.cells, vec![]);
+    }
+}
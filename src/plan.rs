@@ -1,135 +1,284 @@
 extern crate pcf;
-extern crate regex;
 
+use crate::board::{bit_position, bitboard_bit, BITBOARD_ROWS, WIDTH};
 use enumset::{enum_set, EnumSet, EnumSetType};
 use pcf::{Piece, PieceState, Placement, Rotation};
-use regex::Regex;
+use serde::{Deserialize, Serialize};
 
 pub type Cells = [(i32, i32, EnumSet<Direction>); 4];
 
+/// One placement of a solution in field coordinates, independent of `pcf` types so that
+/// solutions can be stored.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Step {
+    pub piece: u32,
+    pub cells: [(i32, i32); 4],
+}
+
+impl Step {
+    pub fn from_placement(placement: &Placement) -> Step {
+        let piece = pcf::PIECES
+            .iter()
+            .position(|&p| p == placement.kind.piece())
+            .unwrap() as u32;
+        let mut cells = [(0, 0); 4];
+        for (c, p) in cells.iter_mut().zip(mask_positions(placement.board().0)) {
+            *c = p;
+        }
+        Step { piece, cells }
+    }
+
+    /// Cells with the directions of their neighbours in the same piece.
+    pub fn cells(&self) -> Cells {
+        connect(&self.cells)
+    }
+
+    /// The same step after the rows in `cleared_rows` (bit `y` for row `y`) were cleared
+    /// below it.
+    pub fn after_clear(&self, cleared_rows: u64) -> Step {
+        let mut step = *self;
+        for (_, y) in &mut step.cells {
+            *y -= (cleared_rows & ((1 << *y) - 1)).count_ones() as i32;
+        }
+        step
+    }
+}
+
 pub trait PlanPlacement {
     fn cells(&self) -> Cells;
 }
 
 impl PlanPlacement for Placement {
     fn cells(&self) -> Cells {
-        let mut cells = self.kind.cells();
+        Step::from_placement(self).cells()
+    }
+}
 
-        let min_x = cells.iter().min_by_key(|cell| cell.0).unwrap().0;
-        let min_y = cells.iter().min_by_key(|cell| cell.1).unwrap().1;
+pub trait PlanPiceState {
+    /// The first rotation with the piece state's shape, `None` when a cleared row splits
+    /// the piece.
+    fn rotation(&self) -> Option<Rotation>;
+    /// Cells in the piece state's own board, which has the piece against the left wall.
+    fn cells(&self) -> Cells;
+}
 
-        let mut offset_x = 0;
-        let mut offset_y = 0;
+impl PlanPiceState for PieceState {
+    fn rotation(&self) -> Option<Rotation> {
+        shape_rotation(self.piece(), &mask_positions(self.board().0))
+    }
 
-        if min_y < 0 {
-            offset_y += -min_y;
+    fn cells(&self) -> Cells {
+        let mut positions = [(0, 0); 4];
+        for (p, position) in positions.iter_mut().zip(mask_positions(self.board().0)) {
+            *p = position;
         }
+        connect(&positions)
+    }
+}
 
-        if min_x < 0 {
-            offset_x += -min_x;
-        }
+/// A piece in one rotation and the cells it covers.
+type Shape = (Piece, Rotation, [(i32, i32); 4]);
 
-        for (x, y, _) in &mut cells {
-            *y += self.kind.y() as i32 + offset_y;
-            *x += self.x as i32 + offset_x;
-        }
-        cells
+/// Every shape of each piece, moved so the lowest x and y are 0 and sorted, with the first
+/// rotation that has it. I, S and Z look the same upside down and O in every rotation.
+const SHAPES: [Shape; 19] = [
+    (Piece::I, Rotation::North, [(0, 0), (1, 0), (2, 0), (3, 0)]),
+    (Piece::I, Rotation::East, [(0, 0), (0, 1), (0, 2), (0, 3)]),
+    (Piece::O, Rotation::North, [(0, 0), (0, 1), (1, 0), (1, 1)]),
+    (Piece::T, Rotation::North, [(0, 0), (1, 0), (1, 1), (2, 0)]),
+    (Piece::T, Rotation::East, [(0, 0), (0, 1), (0, 2), (1, 1)]),
+    (Piece::T, Rotation::South, [(0, 1), (1, 0), (1, 1), (2, 1)]),
+    (Piece::T, Rotation::West, [(0, 1), (1, 0), (1, 1), (1, 2)]),
+    (Piece::L, Rotation::North, [(0, 0), (1, 0), (2, 0), (2, 1)]),
+    (Piece::L, Rotation::East, [(0, 0), (0, 1), (0, 2), (1, 0)]),
+    (Piece::L, Rotation::South, [(0, 0), (0, 1), (1, 1), (2, 1)]),
+    (Piece::L, Rotation::West, [(0, 2), (1, 0), (1, 1), (1, 2)]),
+    (Piece::J, Rotation::North, [(0, 0), (0, 1), (1, 0), (2, 0)]),
+    (Piece::J, Rotation::East, [(0, 0), (0, 1), (0, 2), (1, 2)]),
+    (Piece::J, Rotation::South, [(0, 1), (1, 1), (2, 0), (2, 1)]),
+    (Piece::J, Rotation::West, [(0, 0), (1, 0), (1, 1), (1, 2)]),
+    (Piece::S, Rotation::North, [(0, 0), (1, 0), (1, 1), (2, 1)]),
+    (Piece::S, Rotation::East, [(0, 1), (0, 2), (1, 0), (1, 1)]),
+    (Piece::Z, Rotation::North, [(0, 1), (1, 0), (1, 1), (2, 0)]),
+    (Piece::Z, Rotation::East, [(0, 0), (0, 1), (1, 1), (1, 2)]),
+];
+
+/// Filled cells of a `pcf::BitBoard` mask.
+fn mask_positions(mask: u64) -> Vec<(i32, i32)> {
+    (0..(WIDTH * BITBOARD_ROWS) as u32)
+        .filter(|bit| mask & 1 << bit != 0)
+        .map(bit_position)
+        .collect()
+}
+
+/// The `pcf::BitBoard` bits of `positions`.
+pub fn positions_mask(positions: &[(i32, i32)]) -> u64 {
+    positions
+        .iter()
+        .fold(0, |mask, &(x, y)| mask | bitboard_bit(x, y))
+}
+
+fn min_corner(positions: impl Iterator<Item = (i32, i32)>) -> (i32, i32) {
+    positions.fold((i32::MAX, i32::MAX), |(min_x, min_y), (x, y)| {
+        (min_x.min(x), min_y.min(y))
+    })
+}
+
+/// `positions` moved so the lowest x and y are 0, in a fixed order.
+fn normalize(positions: &[(i32, i32)]) -> Vec<(i32, i32)> {
+    let (min_x, min_y) = min_corner(positions.iter().copied());
+    let mut result: Vec<_> = positions
+        .iter()
+        .map(|&(x, y)| (x - min_x, y - min_y))
+        .collect();
+    result.sort();
+    result
+}
+
+/// The first rotation of `piece` with the shape of `positions`, `None` if no rotation has
+/// it.
+fn shape_rotation(piece: Piece, positions: &[(i32, i32)]) -> Option<Rotation> {
+    let shape = normalize(positions);
+    SHAPES
+        .iter()
+        .find(|(p, _, cells)| *p == piece && cells[..] == shape[..])
+        .map(|&(_, rotation, _)| rotation)
+}
+
+/// `positions` with the directions of their neighbours among them. A piece split by a
+/// cleared row gets two parts, which is how it looks on the field.
+fn connect(positions: &[(i32, i32); 4]) -> Cells {
+    let mut cells = [(0, 0, EnumSet::new()); 4];
+    for (c, &(x, y)) in cells.iter_mut().zip(positions.iter()) {
+        *c = (x, y, neighbours(positions, x, y));
     }
+    cells
 }
 
-pub trait PlanPiceState {
-    fn cells(&self) -> Cells;
+/// The directions of the cells of `positions` next to `(x, y)`.
+fn neighbours(positions: &[(i32, i32)], x: i32, y: i32) -> EnumSet<Direction> {
+    use Direction::*;
+
+    [
+        (Up, (x, y + 1)),
+        (Down, (x, y - 1)),
+        (Left, (x - 1, y)),
+        (Right, (x + 1, y)),
+    ]
+    .iter()
+    .filter(|(_, p)| positions.contains(p))
+    .map(|&(d, _)| d)
+    .collect()
 }
 
-impl PlanPiceState for PieceState {
-    fn cells(&self) -> Cells {
-        use Direction::*;
-
-        let mut cells = match self.piece() {
-            Piece::I => [
-                (-1, 0, enum_set!(Right)),
-                (0, 0, enum_set!(Left | Right)),
-                (1, 0, enum_set!(Left | Right)),
-                (2, 0, enum_set!(Left)),
-            ],
-            Piece::O => [
-                (0, 0, enum_set!(Right | Up)),
-                (1, 0, enum_set!(Left | Up)),
-                (0, 1, enum_set!(Right | Down)),
-                (1, 1, enum_set!(Left | Down)),
-            ],
-            Piece::L => [
-                (-1, 0, enum_set!(Right)),
-                (0, 0, enum_set!(Left | Right)),
-                (1, 0, enum_set!(Left | Up)),
-                (1, 1, enum_set!(Down)),
-            ],
-            Piece::J => [
-                (-1, 0, enum_set!(Right | Up)),
-                (0, 0, enum_set!(Left | Right)),
-                (1, 0, enum_set!(Left)),
-                (-1, 1, enum_set!(Down)),
-            ],
-            Piece::T => [
-                (-1, 0, enum_set!(Right)),
-                (0, 0, enum_set!(Left | Right | Up)),
-                (1, 0, enum_set!(Left)),
-                (0, 1, enum_set!(Down)),
-            ],
-            Piece::S => [
-                (-1, 0, enum_set!(Right)),
-                (0, 0, enum_set!(Left | Up)),
-                (0, 1, enum_set!(Down | Right)),
-                (1, 1, enum_set!(Left)),
-            ],
-            Piece::Z => [
-                (-1, 1, enum_set!(Right)),
-                (0, 1, enum_set!(Left | Down)),
-                (0, 0, enum_set!(Up | Right)),
-                (1, 0, enum_set!(Left)),
-            ],
-        };
-
-        let enum_name = format!("{:?}", self);
-        let rotation: Rotation;
-        if enum_name.contains("O") {
-            println!("O");
-            rotation = Rotation::North;
-        } else {
-            let re = Regex::new(r"\A[SZTJLOI](.+?)\d+\z").unwrap();
-            let matched = re.captures(&enum_name).unwrap().at(1).unwrap();
-            println!("matched: {}", matched);
-            rotation = match matched.to_string().as_str() {
-                "North" => Rotation::North,
-                "East" => Rotation::East,
-                "South" => Rotation::South,
-                "West" => Rotation::West,
-                "Vertical" => Rotation::East,
-                _ => Rotation::North,
+/// Checks that `cells` are 4 distinct cells joined edge to edge, each connected in exactly
+/// the directions of its neighbours. The sprite for a cell is chosen by its directions, so
+/// any mismatch draws a broken outline.
+pub fn validate(cells: &Cells) -> Result<(), String> {
+    let positions: Vec<_> = cells.iter().map(|&(x, y, _)| (x, y)).collect();
+    if normalize(&positions).windows(2).any(|w| w[0] == w[1]) {
+        return Err(format!("repeated cell in {:?}", positions));
+    }
+
+    let mut reached = vec![positions[0]];
+    let mut i = 0;
+    while i < reached.len() {
+        let (x, y) = reached[i];
+        for &p in &[(x, y + 1), (x, y - 1), (x - 1, y), (x + 1, y)] {
+            if positions.contains(&p) && !reached.contains(&p) {
+                reached.push(p);
             }
         }
+        i += 1;
+    }
+    if reached.len() != positions.len() {
+        return Err(format!("cells {:?} are not connected", positions));
+    }
 
-        for (x, y, d) in &mut cells {
-            match rotation {
-                Rotation::North => {}
-                Rotation::East => {
-                    *x = -*x;
-                    std::mem::swap(x, y);
-                    *d = d.iter().map(Direction::cw).collect();
-                }
-                Rotation::South => {
-                    *x = -*x;
-                    *y = -*y;
-                    *d = d.iter().map(Direction::flip).collect();
-                }
-                Rotation::West => {
-                    *y = -*y;
-                    std::mem::swap(x, y);
-                    *d = d.iter().map(Direction::ccw).collect();
-                }
+    for &(x, y, d) in cells {
+        let expected = neighbours(&positions, x, y);
+        if d != expected {
+            return Err(format!(
+                "cell {:?} has directions {:?}, neighbours are {:?}",
+                (x, y),
+                d,
+                expected
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Cells of `piece` in its spawn orientation, relative to its rotation centre.
+pub fn piece_cells(piece: Piece) -> Cells {
+    use Direction::*;
+
+    match piece {
+        Piece::I => [
+            (-1, 0, enum_set!(Right)),
+            (0, 0, enum_set!(Left | Right)),
+            (1, 0, enum_set!(Left | Right)),
+            (2, 0, enum_set!(Left)),
+        ],
+        Piece::O => [
+            (0, 0, enum_set!(Right | Up)),
+            (1, 0, enum_set!(Left | Up)),
+            (0, 1, enum_set!(Right | Down)),
+            (1, 1, enum_set!(Left | Down)),
+        ],
+        Piece::L => [
+            (-1, 0, enum_set!(Right)),
+            (0, 0, enum_set!(Left | Right)),
+            (1, 0, enum_set!(Left | Up)),
+            (1, 1, enum_set!(Down)),
+        ],
+        Piece::J => [
+            (-1, 0, enum_set!(Right | Up)),
+            (0, 0, enum_set!(Left | Right)),
+            (1, 0, enum_set!(Left)),
+            (-1, 1, enum_set!(Down)),
+        ],
+        Piece::T => [
+            (-1, 0, enum_set!(Right)),
+            (0, 0, enum_set!(Left | Right | Up)),
+            (1, 0, enum_set!(Left)),
+            (0, 1, enum_set!(Down)),
+        ],
+        Piece::S => [
+            (-1, 0, enum_set!(Right)),
+            (0, 0, enum_set!(Left | Up)),
+            (0, 1, enum_set!(Down | Right)),
+            (1, 1, enum_set!(Left)),
+        ],
+        Piece::Z => [
+            (-1, 1, enum_set!(Right)),
+            (0, 1, enum_set!(Left | Down)),
+            (0, 0, enum_set!(Up | Right)),
+            (1, 0, enum_set!(Left)),
+        ],
+    }
+}
+
+pub fn rotate_cells(cells: &mut Cells, rotation: Rotation) {
+    for (x, y, d) in cells {
+        match rotation {
+            Rotation::North => {}
+            Rotation::East => {
+                *x = -*x;
+                std::mem::swap(x, y);
+                *d = d.iter().map(Direction::cw).collect();
+            }
+            Rotation::South => {
+                *x = -*x;
+                *y = -*y;
+                *d = d.iter().map(Direction::flip).collect();
+            }
+            Rotation::West => {
+                *y = -*y;
+                std::mem::swap(x, y);
+                *d = d.iter().map(Direction::ccw).collect();
             }
         }
-        cells
     }
 }
 
@@ -169,3 +318,164 @@ impl Direction {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::srs::ROTATIONS;
+    use crate::test_util;
+    use pcf::BitBoard;
+
+    /// Boards covering every piece state `pcf` has: an empty field, and fields with full
+    /// rows that pieces are placed across.
+    fn boards() -> Vec<BitBoard> {
+        let row = |y: u32| 0x3ff << (10 * y);
+        vec![
+            BitBoard(0),
+            BitBoard(row(0)),
+            BitBoard(row(1)),
+            BitBoard(row(2)),
+            BitBoard(row(1) | row(2)),
+            BitBoard(row(0) | row(2)),
+        ]
+    }
+
+    #[test]
+    fn shape_table_matches_the_rotated_pieces() {
+        for &piece in &pcf::PIECES {
+            for &rotation in &ROTATIONS {
+                let mut cells = piece_cells(piece);
+                rotate_cells(&mut cells, rotation);
+                let positions: Vec<_> = cells.iter().map(|&(x, y, _)| (x, y)).collect();
+                let found = shape_rotation(piece, &positions).unwrap();
+                let mut first = piece_cells(piece);
+                rotate_cells(&mut first, found);
+                let first: Vec<_> = first.iter().map(|&(x, y, _)| (x, y)).collect();
+                assert_eq!(normalize(&first), normalize(&positions), "{:?}", piece);
+                assert!(
+                    ROTATIONS.iter().position(|&r| r == found)
+                        <= ROTATIONS.iter().position(|&r| r == rotation)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn every_piece_state_decodes_to_its_mask() {
+        for board in boards() {
+            for &piece in &pcf::PIECES {
+                for placement in test_util::placements(board, &[piece]) {
+                    let step = Step::from_placement(&placement);
+                    assert_eq!(positions_mask(&step.cells), placement.board().0);
+                    let cells = PlanPlacement::cells(&placement);
+                    let state = PlanPiceState::cells(&placement.kind);
+                    let positions: Vec<_> = state.iter().map(|&(x, y, _)| (x, y)).collect();
+                    assert_eq!(positions_mask(&positions), placement.kind.board().0);
+
+                    // A piece is only split when a full row lies between its cells.
+                    let rows: Vec<_> = step.cells.iter().map(|&(_, y)| y).collect();
+                    let (low, high) = (*rows.iter().min().unwrap(), *rows.iter().max().unwrap());
+                    let split = (low..=high).any(|y| !rows.contains(&y));
+                    assert_eq!(validate(&cells).is_ok(), !split, "{:?}", placement);
+                    assert_eq!(
+                        placement.kind.rotation().is_some(),
+                        !split,
+                        "{:?}",
+                        placement
+                    );
+                    if !split {
+                        assert_eq!(validate(&state), Ok(()), "{:?}", placement);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn split_masks_have_no_rotation() {
+        let split_i = [(0, 0), (0, 1), (0, 3), (0, 4)];
+        assert_eq!(shape_rotation(Piece::I, &split_i), None);
+        assert_eq!(
+            shape_rotation(Piece::T, &[(0, 0), (1, 0), (2, 0), (1, 1)]),
+            Some(Rotation::North)
+        );
+        assert_eq!(
+            shape_rotation(Piece::L, &[(0, 0), (1, 0), (2, 0), (1, 1)]),
+            None
+        );
+        let cells = connect(&split_i);
+        assert_eq!(cells[1].2, enum_set!(Direction::Down));
+        assert_eq!(cells[2].2, enum_set!(Direction::Up));
+    }
+
+    /// Every piece in every rotation at every position where it fits on the field.
+    fn all_placed_cells() -> Vec<(Piece, Rotation, Cells)> {
+        let mut all = vec![];
+        for &piece in &pcf::PIECES {
+            for &rotation in &ROTATIONS {
+                for x in 0..WIDTH as i32 {
+                    for y in 0..BITBOARD_ROWS as i32 {
+                        let mut cells = piece_cells(piece);
+                        rotate_cells(&mut cells, rotation);
+                        for (cx, cy, _) in &mut cells {
+                            *cx += x;
+                            *cy += y;
+                        }
+                        if cells.iter().all(|&(cx, cy, _)| {
+                            cx >= 0 && cx < WIDTH as i32 && cy >= 0 && cy < BITBOARD_ROWS as i32
+                        }) {
+                            all.push((piece, rotation, cells));
+                        }
+                    }
+                }
+            }
+        }
+        all
+    }
+
+    #[test]
+    fn rotated_pieces_are_valid_everywhere() {
+        let all = all_placed_cells();
+        assert!(all.len() > 7 * 4 * 20);
+        for (piece, rotation, cells) in all {
+            assert_eq!(validate(&cells), Ok(()), "{:?} {:?}", piece, rotation);
+
+            let mut positions = [(0, 0); 4];
+            for (p, &(x, y, _)) in positions.iter_mut().zip(cells.iter()) {
+                *p = (x, y);
+            }
+            // Directions found from the positions alone agree with the rotated ones.
+            assert_eq!(connect(&positions), cells, "{:?} {:?}", piece, rotation);
+            assert_eq!(
+                normalize(&mask_positions(positions_mask(&positions))),
+                normalize(&positions)
+            );
+            assert!(shape_rotation(piece, &positions).is_some());
+        }
+    }
+
+    #[test]
+    fn validate_rejects_broken_cells() {
+        for (piece, rotation, cells) in all_placed_cells() {
+            for i in 0..4 {
+                for &d in &[
+                    Direction::Up,
+                    Direction::Down,
+                    Direction::Left,
+                    Direction::Right,
+                ] {
+                    let mut broken = cells;
+                    broken[i].2 ^= d;
+                    assert!(validate(&broken).is_err(), "{:?} {:?}", piece, rotation);
+                }
+                let mut repeated = cells;
+                repeated[i] = cells[(i + 1) % 4];
+                assert!(validate(&repeated).is_err(), "{:?} {:?}", piece, rotation);
+            }
+            let mut apart = cells;
+            apart[0].0 += 5;
+            apart[0].2 = EnumSet::new();
+            assert!(validate(&apart).is_err(), "{:?} {:?}", piece, rotation);
+        }
+    }
+}
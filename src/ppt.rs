@@ -2,6 +2,8 @@ extern crate process_memory;
 use std::mem::MaybeUninit;
 
 use process_memory::{DataMember, Memory, ProcessHandle};
+
+use pc_assist::board::Board;
 use winapi::{
     shared::minwindef::HMODULE,
     um::psapi::{EnumProcessModules, GetModuleFileNameExA},
@@ -124,6 +126,15 @@ impl Ppt {
         return Ok(Some(hold));
     }
 
+    pub fn get_board(&self, index: u32) -> std::io::Result<Board> {
+        Ok(Board {
+            columns: self.get_columns(index)?,
+            current_piece: self.get_current_piece(index),
+            hold: self.get_hold(index)?,
+            next_pieces: self.get_next_pieces(index)?,
+        })
+    }
+
     pub fn get_player_count(&self) -> std::io::Result<u32> {
         return Ok(1);
 
@@ -0,0 +1,245 @@
+extern crate image;
+
+use crate::board::Board;
+use crate::layout::{FieldPlacement, Layout, Rect};
+use crate::overlay::{Align, Overlay, OverlayEvent, OverlayRenderer, Sprite};
+use crate::plan::Step;
+use crate::theme::Theme;
+use image::imageops::{self, FilterType};
+use image::{Rgba, RgbaImage};
+use std::path::Path;
+use std::sync::Arc;
+
+const PLAN_SPRITES: [&[u8]; 16] = [
+    include_bytes!("../sprites/plan.0.png"),
+    include_bytes!("../sprites/plan.1.png"),
+    include_bytes!("../sprites/plan.2.png"),
+    include_bytes!("../sprites/plan.3.png"),
+    include_bytes!("../sprites/plan.4.png"),
+    include_bytes!("../sprites/plan.5.png"),
+    include_bytes!("../sprites/plan.6.png"),
+    include_bytes!("../sprites/plan.7.png"),
+    include_bytes!("../sprites/plan.8.png"),
+    include_bytes!("../sprites/plan.9.png"),
+    include_bytes!("../sprites/plan.10.png"),
+    include_bytes!("../sprites/plan.11.png"),
+    include_bytes!("../sprites/plan.12.png"),
+    include_bytes!("../sprites/plan.13.png"),
+    include_bytes!("../sprites/plan.14.png"),
+    include_bytes!("../sprites/plan.15.png"),
+];
+const PIECE_SPRITES: [&[u8]; 7] = [
+    include_bytes!("../sprites/piece.0.png"),
+    include_bytes!("../sprites/piece.1.png"),
+    include_bytes!("../sprites/piece.2.png"),
+    include_bytes!("../sprites/piece.3.png"),
+    include_bytes!("../sprites/piece.4.png"),
+    include_bytes!("../sprites/piece.5.png"),
+    include_bytes!("../sprites/piece.6.png"),
+];
+const FILL_SPRITE: &[u8] = include_bytes!("../sprites/fill.png");
+
+/// The overlay sprites decoded for drawing without a GPU.
+pub struct Sprites {
+    /// Indexed by the bits of a cell's directions, like the overlay's `plan` sprites.
+    pub plan: Vec<RgbaImage>,
+    /// Indexed by `pcf::Piece`.
+    pub piece: Vec<RgbaImage>,
+    pub fill: RgbaImage,
+}
+
+fn decode(data: &&[u8]) -> RgbaImage {
+    image::load_from_memory(data)
+        .expect("sprite is not a valid PNG")
+        .to_rgba8()
+}
+
+impl Sprites {
+    pub fn load() -> Sprites {
+        Sprites {
+            plan: PLAN_SPRITES.iter().map(decode).collect(),
+            piece: PIECE_SPRITES.iter().map(decode).collect(),
+            fill: decode(&FILL_SPRITE),
+        }
+    }
+
+    /// The built in sprites with those found in `dir` in their place. Files are named like
+    /// the built in ones, and any that are missing keep the built in sprite.
+    pub fn load_dir(dir: impl AsRef<Path>) -> std::io::Result<Sprites> {
+        let dir = dir.as_ref();
+        let replace = |sprite: &mut RgbaImage, name: String| -> std::io::Result<()> {
+            let path = dir.join(name);
+            if path.exists() {
+                *sprite = image::open(&path)
+                    .map_err(|e| {
+                        std::io::Error::new(
+                            std::io::ErrorKind::InvalidData,
+                            format!("{}: {}", path.display(), e),
+                        )
+                    })?
+                    .to_rgba8();
+            }
+            Ok(())
+        };
+        let mut sprites = Sprites::load();
+        for (i, sprite) in sprites.plan.iter_mut().enumerate() {
+            replace(sprite, format!("plan.{}.png", i))?;
+        }
+        for (i, sprite) in sprites.piece.iter_mut().enumerate() {
+            replace(sprite, format!("piece.{}.png", i))?;
+        }
+        replace(&mut sprites.fill, "fill.png".to_string())?;
+        Ok(sprites)
+    }
+}
+
+/// Alpha blends `src` onto `dst` with its top left corner at `(x, y)`, clipping at the
+/// edges of `dst`.
+fn blend(dst: &mut RgbaImage, src: &RgbaImage, x: i32, y: i32, tint: [u8; 4]) {
+    for (sx, sy, pixel) in src.enumerate_pixels() {
+        let (dx, dy) = (x + sx as i32, y + sy as i32);
+        if dx < 0 || dy < 0 || dx >= dst.width() as i32 || dy >= dst.height() as i32 {
+            continue;
+        }
+        let alpha = pixel[3] as u32 * tint[3] as u32 / 255;
+        let out = dst.get_pixel_mut(dx as u32, dy as u32);
+        for c in 0..3 {
+            let top = pixel[c] as u32 * tint[c] as u32 / 255;
+            out[c] = ((top * alpha + out[c] as u32 * (255 - alpha)) / 255) as u8;
+        }
+        out[3] = (alpha + out[3] as u32 * (255 - alpha) / 255) as u8;
+    }
+}
+
+fn draw_sprite(dst: &mut RgbaImage, sprite: &RgbaImage, rect: Rect, tint: [u8; 4]) {
+    let scaled = imageops::resize(
+        sprite,
+        rect.width.round().max(1.0) as u32,
+        rect.height.round().max(1.0) as u32,
+        FilterType::Triangle,
+    );
+    blend(
+        dst,
+        &scaled,
+        rect.x.round() as i32,
+        rect.y.round() as i32,
+        tint,
+    );
+}
+
+/// Text drawn on a `Canvas`, positioned in client pixels like `Rect`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Text {
+    pub text: String,
+    pub x: f32,
+    pub y: f32,
+    /// Height of the font in pixels.
+    pub size: f32,
+}
+
+/// An image the overlay can be drawn into. Text is not rasterized but recorded in `texts`.
+pub struct Canvas<'a> {
+    pub image: RgbaImage,
+    pub texts: Vec<Text>,
+    layout: Layout,
+    sprites: &'a Sprites,
+}
+
+impl OverlayRenderer for Canvas<'_> {
+    fn sprite(&mut self, sprite: Sprite, x: f32, y: f32, cell: f32, tint: [u8; 4]) {
+        let image = match sprite {
+            Sprite::Plan(i) => &self.sprites.plan[i],
+            Sprite::Piece(i) => &self.sprites.piece[i],
+            Sprite::Fill => &self.sprites.fill,
+        };
+        let scale = cell / sprite.pixels_per_cell();
+        let rect = self.layout.picture_rect(
+            x,
+            y,
+            image.width() as f32 * scale,
+            image.height() as f32 * scale,
+        );
+        draw_sprite(&mut self.image, image, rect, tint);
+    }
+
+    fn text(&mut self, text: &str, x: f32, y: f32, _: Align, _: [u8; 4], size: f32) {
+        if !text.is_empty() {
+            let (x, y) = self.layout.picture_point(x, y);
+            self.texts.push(Text {
+                text: text.to_string(),
+                x,
+                y,
+                size: size * self.layout.pixels_per_cell(),
+            });
+        }
+    }
+}
+
+/// Draws frames of the overlay into images, for machines without a GPU.
+pub struct Renderer {
+    pub sprites: Sprites,
+    pub layout: Layout,
+    pub field: FieldPlacement,
+    pub theme: Theme,
+}
+
+impl Renderer {
+    pub fn new(width: u32, height: u32, field: FieldPlacement) -> Renderer {
+        Renderer {
+            sprites: Sprites::load(),
+            layout: Layout::new(width as f32, height as f32),
+            field,
+            theme: Theme::default(),
+        }
+    }
+
+    /// Draws plans with `theme`, loading its sprite directory if it has one.
+    pub fn set_theme(&mut self, theme: Theme) -> std::io::Result<()> {
+        if let Some(dir) = &theme.sprites {
+            self.sprites = Sprites::load_dir(dir)?;
+        }
+        self.theme = theme;
+        Ok(())
+    }
+
+    /// A canvas filled with `background`.
+    pub fn canvas(&self, background: [u8; 4]) -> Canvas<'_> {
+        Canvas {
+            image: RgbaImage::from_pixel(
+                self.layout.client_width as u32,
+                self.layout.client_height as u32,
+                Rgba(background),
+            ),
+            texts: vec![],
+            layout: self.layout,
+            sprites: &self.sprites,
+        }
+    }
+
+    /// Draws `board` and `plan` on a black background, the whole plan if `full_path` is
+    /// set and otherwise its first step.
+    pub fn render(&self, board: &Board, plan: &[Step], full_path: bool) -> RgbaImage {
+        let mut canvas = self.canvas([0, 0, 0, 255]);
+        let mut overlay = Overlay::new(self.field);
+        overlay.show_board = true;
+        overlay.full_path = full_path;
+        overlay.theme = self.theme.clone();
+        overlay.handle(OverlayEvent::Board(board.clone()));
+        overlay.handle(OverlayEvent::Plan(Arc::new(plan.first().copied())));
+        overlay.handle(OverlayEvent::Path(plan.to_vec()));
+        overlay.draw(&mut canvas);
+        canvas.image
+    }
+
+    pub fn save_png(
+        &self,
+        path: impl AsRef<Path>,
+        board: &Board,
+        plan: &[Step],
+        full_path: bool,
+    ) -> std::io::Result<()> {
+        self.render(board, plan, full_path)
+            .save(path)
+            .map_err(|e| std::io::Error::other(e.to_string()))
+    }
+}
@@ -0,0 +1,185 @@
+extern crate pcf;
+
+use crate::board::{Board, HEIGHT, WIDTH};
+use crate::finesse::{self, Input};
+use crate::history::Move;
+use crate::plan::Step;
+use crate::srs::{FallingPiece, Field};
+
+/// Pieces shown in the next queue, like the game.
+pub const NEXT_PIECES: usize = 5;
+
+/// xorshift64*, enough to deal the same pieces again from a seed.
+#[derive(Clone, Debug)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        // The state must never be 0.
+        Rng((seed ^ 0x9E37_79B9_7F4A_7C15).max(1))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// A number in `0..n`.
+    pub fn below(&mut self, n: u32) -> u32 {
+        (self.next_u64() % n as u64) as u32
+    }
+}
+
+/// Deals pieces in shuffled bags of all 7, as PPT indices.
+#[derive(Clone, Debug)]
+pub struct Bag {
+    rng: Rng,
+    pieces: Vec<u32>,
+}
+
+impl Bag {
+    pub fn new(seed: u64) -> Bag {
+        Bag {
+            rng: Rng::new(seed),
+            pieces: vec![],
+        }
+    }
+
+    /// The next piece, starting a new bag when this one is empty.
+    pub fn deal(&mut self) -> u32 {
+        if self.pieces.is_empty() {
+            self.pieces = (0..7).collect();
+            for i in (1..7).rev() {
+                let j = self.rng.below(i as u32 + 1) as usize;
+                self.pieces.swap(i, j);
+            }
+        }
+        self.pieces.pop().unwrap()
+    }
+}
+
+/// A game without PPT2: a 10x40 field with SRS, a 7-bag, hold and line clears.
+///
+/// `board` holds the locked cells, current piece, hold and next queue like a `Board` read
+/// from the game, so it can be fed to the solver and overlay the same way.
+#[derive(Clone, Debug)]
+pub struct Simulator {
+    pub board: Board,
+    pub falling: Option<FallingPiece>,
+    pub topped_out: bool,
+    hold_used: bool,
+    bag: Bag,
+}
+
+impl Simulator {
+    pub fn new(seed: u64) -> Simulator {
+        let mut bag = Bag::new(seed);
+        let next_pieces = (0..NEXT_PIECES).map(|_| bag.deal()).collect();
+        let mut sim = Simulator {
+            board: Board {
+                columns: vec![vec![-1; HEIGHT]; WIDTH],
+                current_piece: None,
+                hold: None,
+                next_pieces,
+            },
+            falling: None,
+            topped_out: false,
+            hold_used: false,
+            bag,
+        };
+        sim.spawn_next();
+        sim
+    }
+
+    fn spawn(&mut self, piece: u32) {
+        let falling = FallingPiece::spawn(pcf::PIECES[piece as usize]);
+        self.topped_out = !falling.fits(&Field::from_board(&self.board));
+        self.board.current_piece = Some(piece);
+        self.falling = Some(falling);
+    }
+
+    fn spawn_next(&mut self) {
+        let piece = self.board.next_pieces.remove(0);
+        self.board.next_pieces.push(self.bag.deal());
+        self.spawn(piece);
+    }
+
+    /// The current piece followed by the next `n` pieces that will be dealt.
+    pub fn upcoming(&self, n: usize) -> Vec<u32> {
+        let mut bag = self.bag.clone();
+        self.board
+            .current_piece
+            .into_iter()
+            .chain(self.board.next_pieces.iter().copied())
+            .chain(std::iter::repeat_with(|| bag.deal()))
+            .take(n)
+            .collect()
+    }
+
+    /// The falling piece as a step, for drawing it.
+    pub fn falling_step(&self) -> Option<Step> {
+        Some(Step {
+            piece: self.board.current_piece?,
+            cells: self.falling?.cells(),
+        })
+    }
+
+    /// Applies one input, returning the move when it locks a piece. Inputs do nothing
+    /// after topping out.
+    pub fn input(&mut self, input: Input) -> Option<Move> {
+        let falling = self.falling.filter(|_| !self.topped_out)?;
+        let field = Field::from_board(&self.board);
+        match input {
+            Input::Hold => {
+                if !self.hold_used {
+                    let current = self.board.current_piece?;
+                    match self.board.hold.replace(current) {
+                        Some(held) => self.spawn(held),
+                        None => self.spawn_next(),
+                    }
+                    self.hold_used = true;
+                }
+                None
+            }
+            Input::HardDrop => Some(self.lock(falling.sonic_drop(&field))),
+            _ => {
+                if let Some(moved) = finesse::apply(&falling, &field, input) {
+                    self.falling = Some(moved);
+                }
+                None
+            }
+        }
+    }
+
+    fn lock(&mut self, piece: FallingPiece) -> Move {
+        let code = self.board.current_piece.unwrap() as i32;
+        for (x, y) in piece.cells().iter() {
+            self.board.columns[*x as usize][*y as usize] = code;
+        }
+
+        let mut cleared_rows = 0;
+        for y in (0..HEIGHT).rev() {
+            if self.board.columns.iter().all(|column| column[y] != -1) {
+                cleared_rows |= 1 << y;
+                for column in &mut self.board.columns {
+                    column.remove(y);
+                    column.push(-1);
+                }
+            }
+        }
+
+        let m = Move {
+            piece: code as u32,
+            rotation: piece.rotation,
+            x: piece.x,
+            y: piece.y,
+            hold: self.hold_used,
+            cleared_rows,
+        };
+        self.hold_used = false;
+        self.spawn_next();
+        m
+    }
+}
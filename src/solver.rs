@@ -0,0 +1,372 @@
+extern crate pcf;
+
+use crate::board::Board;
+use crate::config::{Placeability, SolverConfig};
+use crate::finesse;
+use crate::plan::PlanPlacement;
+use crate::srs::Field;
+use pcf::{BitBoard, Piece, Placement, SearchStatus};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+/// Boards whose reachable placements a worker remembers before starting over.
+const REACHABILITY_CACHE: usize = 1 << 14;
+
+/// The cells of every placement a piece can reach on a board, by board and piece.
+type Reachable = HashMap<(u64, usize), HashSet<[(i32, i32); 4]>>;
+
+fn cell_positions(placement: &Placement) -> [(i32, i32); 4] {
+    let mut cells = [(0, 0); 4];
+    for (c, &(x, y, _)) in cells.iter_mut().zip(placement.cells().iter()) {
+        *c = (x, y);
+    }
+    cells
+}
+
+/// Placements where no filled cell is above any of the piece's cells.
+pub fn hard_drop_only(board: BitBoard, placement: Placement) -> bool {
+    let field = Field::from_bitboard(board);
+    cell_positions(&placement)
+        .iter()
+        .all(|&(x, y)| (y + 1..field.rows.len() as i32).all(|above| !field.occupied(x, above)))
+}
+
+/// Placements the piece can reach from its spawn position with SRS. The placements of a
+/// piece on a board are found together the first time one of them is judged.
+#[derive(Default)]
+pub struct SrsReachable {
+    cache: RefCell<Reachable>,
+}
+
+impl SrsReachable {
+    pub fn judge(&self, board: BitBoard, placement: Placement) -> bool {
+        let piece = placement.kind.piece();
+        let mut cells = cell_positions(&placement);
+        cells.sort();
+        let mut cache = self.cache.borrow_mut();
+        if cache.len() >= REACHABILITY_CACHE {
+            cache.clear();
+        }
+        cache
+            .entry((board.0, piece as usize))
+            .or_insert_with(|| finesse::reachable(&Field::from_bitboard(board), piece, false))
+            .contains(&cells)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SolverStatus {
+    /// `nodes` is the number of placements examined so far.
+    Searching {
+        nodes: u64,
+    },
+    Found,
+    NoPc,
+    TimedOut,
+    /// The board was not searched because the solver is paused.
+    Paused,
+}
+
+/// Tells whether `placement` can be played on `board`, remembering SRS reachability in
+/// `srs` between calls.
+fn placeable(
+    placeability: Placeability,
+    srs: &SrsReachable,
+    board: BitBoard,
+    placement: Placement,
+) -> bool {
+    match placeability {
+        Placeability::HardDrop => hard_drop_only(board, placement),
+        Placeability::SimpleSrs => pcf::placeability::simple_srs_spins(board, placement),
+        Placeability::Srs => srs.judge(board, placement),
+    }
+}
+
+/// The placements `placeable` accepts that can start a solution of `queue` on `board`, in
+/// the order `pcf` considers them. Every one is rejected, which keeps the search from
+/// going deeper.
+pub(crate) fn first_placements(
+    queue: &[Piece],
+    board: BitBoard,
+    hold: bool,
+    placeable: impl Fn(BitBoard, Placement) -> bool,
+) -> Vec<Placement> {
+    let found = RefCell::new(vec![]);
+    pcf::solve_pc(
+        queue,
+        board,
+        hold,
+        true,
+        |board, placement| {
+            let mut found = found.borrow_mut();
+            if !found.contains(&placement) && placeable(board, placement) {
+                found.push(placement);
+            }
+            false
+        },
+        |_| SearchStatus::Abort,
+    );
+    found.into_inner()
+}
+
+/// The board and queue left after playing `first`, a placement of the first or, with
+/// hold, the second piece of `queue`.
+///
+/// `pcf` keeps cleared lines in the board, so the placement is simply added to it. A held
+/// piece can be played as if it were the current piece, so after holding the first piece
+/// it goes back to the front of the queue.
+fn after_first(queue: &[Piece], board: BitBoard, first: Placement) -> (BitBoard, Vec<Piece>) {
+    let board = BitBoard(board.0 | first.board().0);
+    if first.kind.piece() == queue[0] {
+        (board, queue[1..].to_vec())
+    } else {
+        let mut rest = vec![queue[0]];
+        rest.extend_from_slice(&queue[2..]);
+        (board, rest)
+    }
+}
+
+/// State shared by the workers of one search.
+struct Search {
+    nodes: AtomicU64,
+    /// Set by the progress thread once the time budget is spent.
+    timed_out: AtomicBool,
+    /// Index of the next first placement to search from.
+    next: AtomicUsize,
+    /// Index of the first placement known to lead to all the solutions needed.
+    best: AtomicUsize,
+}
+
+/// Runs `pcf::solve_pc` on `board` with the options in `config`, split over
+/// `config.threads` worker threads. Each worker takes the next possible first placement
+/// and searches the board it leaves.
+///
+/// When several workers find a solution, the one with the earliest first placement wins,
+/// so the result does not depend on the number of threads or their timing. `progress` is
+/// called from another thread every `PROGRESS_INTERVAL` while the search runs. Returns
+/// `Found` with the solution, `NoPc` or `TimedOut`.
+pub fn solve(
+    board: &Board,
+    config: &SolverConfig,
+    progress: impl FnMut(u64) + Send + 'static,
+) -> (SolverStatus, Option<Vec<Placement>>) {
+    let (status, solutions) = solve_alternatives(board, config, 1, progress);
+    (status, solutions.into_iter().next())
+}
+
+/// Like `solve`, but keeps searching until up to `count` different solutions are found.
+/// They are ordered as if the first placements were searched one after another, so the
+/// first one is the solution `solve` would give.
+pub fn solve_alternatives(
+    board: &Board,
+    config: &SolverConfig,
+    count: usize,
+    mut progress: impl FnMut(u64) + Send + 'static,
+) -> (SolverStatus, Vec<Vec<Placement>>) {
+    let count = count.max(1);
+    let mut queue = board.get_queue();
+    if let Some(n) = config.max_pieces {
+        // With hold, placing n pieces can look one piece further.
+        queue.truncate(n + config.hold as usize);
+    }
+
+    let deadline = config
+        .timeout_ms
+        .map(|ms| Instant::now() + Duration::from_millis(ms));
+    let search = Arc::new(Search {
+        nodes: AtomicU64::new(0),
+        timed_out: AtomicBool::new(false),
+        next: AtomicUsize::new(0),
+        best: AtomicUsize::new(usize::MAX),
+    });
+    let done = Arc::new(AtomicBool::new(false));
+
+    let reporter = {
+        let search = Arc::clone(&search);
+        let done = Arc::clone(&done);
+        thread::spawn(move || loop {
+            let wait = deadline.map_or(PROGRESS_INTERVAL, |d| {
+                PROGRESS_INTERVAL.min(d.saturating_duration_since(Instant::now()))
+            });
+            thread::park_timeout(wait);
+            if done.load(Ordering::Relaxed) {
+                break;
+            }
+            if deadline.is_some_and(|d| Instant::now() >= d) {
+                search.timed_out.store(true, Ordering::Relaxed);
+            }
+            progress(search.nodes.load(Ordering::Relaxed));
+        })
+    };
+
+    let initial = board.get_bitboard();
+    let srs = SrsReachable::default();
+    let firsts = Arc::new(first_placements(
+        &queue,
+        initial,
+        config.hold,
+        |board, placement| placeable(config.placeability, &srs, board, placement),
+    ));
+    let workers: Vec<_> = (0..config.threads.max(1).min(firsts.len()))
+        .map(|_| {
+            let search = Arc::clone(&search);
+            let firsts = Arc::clone(&firsts);
+            let queue = queue.clone();
+            let hold = config.hold;
+            let placeability = config.placeability;
+            thread::spawn(move || {
+                let srs = SrsReachable::default();
+                let mut found = vec![];
+                loop {
+                    let index = search.next.fetch_add(1, Ordering::Relaxed);
+                    // A placement before this one already leads to all the solutions that
+                    // will be used.
+                    if index >= firsts.len()
+                        || search.best.load(Ordering::Relaxed) < index
+                        || search.timed_out.load(Ordering::Relaxed)
+                    {
+                        break;
+                    }
+                    let first = firsts[index];
+                    let (board, rest) = after_first(&queue, initial, first);
+                    // `solve_pc` only stops early when the solution callback aborts, so once
+                    // the search is over the judge declines everything to make it unwind.
+                    let aborted = || {
+                        search.timed_out.load(Ordering::Relaxed)
+                            || search.best.load(Ordering::Relaxed) < index
+                    };
+                    let mut solutions = vec![];
+                    pcf::solve_pc(
+                        &rest,
+                        board,
+                        hold,
+                        true,
+                        |board, placement| {
+                            search.nodes.fetch_add(1, Ordering::Relaxed);
+                            !aborted() && placeable(placeability, &srs, board, placement)
+                        },
+                        |s| {
+                            if search.best.load(Ordering::Relaxed) < index {
+                                return SearchStatus::Abort;
+                            }
+                            let mut solution = vec![first];
+                            solution.extend_from_slice(s);
+                            solutions.push(solution);
+                            if solutions.len() >= count {
+                                search.best.fetch_min(index, Ordering::Relaxed);
+                                return SearchStatus::Abort;
+                            }
+                            if search.timed_out.load(Ordering::Relaxed) {
+                                return SearchStatus::Abort;
+                            }
+                            SearchStatus::Continue
+                        },
+                    );
+                    found.push((index, solutions));
+                }
+                found
+            })
+        })
+        .collect();
+    // Taking solutions in the order of their first placement and stopping once there are
+    // enough gives the same result as searching from the placements one after another:
+    // every placement before `best` was searched to the end.
+    let mut found: Vec<_> = workers
+        .into_iter()
+        .flat_map(|worker| worker.join().unwrap_or_default())
+        .collect();
+    found.sort_by_key(|&(index, _)| index);
+    let solutions: Vec<_> = found
+        .into_iter()
+        .flat_map(|(_, solutions)| solutions)
+        .take(count)
+        .collect();
+
+    done.store(true, Ordering::Relaxed);
+    reporter.thread().unpark();
+    reporter.join().ok();
+
+    if !solutions.is_empty() {
+        (SolverStatus::Found, solutions)
+    } else if search.timed_out.load(Ordering::Relaxed) {
+        (SolverStatus::TimedOut, solutions)
+    } else {
+        (SolverStatus::NoPc, solutions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+
+    #[test]
+    fn cached_reachability_agrees_with_find_inputs() {
+        // An empty field and one with an overhang that only a spin gets under.
+        let boards = [BitBoard(0), BitBoard(0b1111001111 << 10 | 0b1111000011)];
+        let srs = SrsReachable::default();
+        for &board in &boards {
+            let field = Field::from_bitboard(board);
+            for &piece in &pcf::PIECES {
+                for placement in test_util::placements(board, &[piece]) {
+                    let inputs =
+                        finesse::find_inputs(&field, piece, cell_positions(&placement), false);
+                    assert_eq!(
+                        srs.judge(board, placement),
+                        inputs.is_some(),
+                        "{:?} on {:b}",
+                        placement,
+                        board.0
+                    );
+                }
+            }
+        }
+    }
+
+    fn config(threads: usize) -> SolverConfig {
+        SolverConfig {
+            timeout_ms: None,
+            threads,
+            ..SolverConfig::default()
+        }
+    }
+
+    #[test]
+    fn thread_count_does_not_change_the_solutions() {
+        let boards = [
+            "current: O\nnext: IIOLJ\n",
+            "current: L\nnext: OJIOI\n",
+            "hold: I\ncurrent: O\nnext: LJIOT\n",
+        ];
+        for text in &boards {
+            let board: Board = text.parse().unwrap();
+            let (status, one) = solve_alternatives(&board, &config(1), 3, |_| {});
+            assert_eq!(status, SolverStatus::Found, "{}", text);
+            for &threads in &[2, 4, 7] {
+                let (_, many) = solve_alternatives(&board, &config(threads), 3, |_| {});
+                assert_eq!(many, one, "{} on {} threads", text, threads);
+            }
+        }
+    }
+
+    #[test]
+    fn solutions_start_with_the_current_or_the_second_piece() {
+        let board: Board = "current: L\nnext: OJIOI\n".parse().unwrap();
+        let (_, solutions) = solve_alternatives(&board, &config(4), 10, |_| {});
+        assert!(!solutions.is_empty());
+        for solution in &solutions {
+            let first = solution[0].kind.piece();
+            assert!(first == Piece::L || first == Piece::O, "{:?}", solution);
+            let cells = solution.iter().fold(0, |b, p| {
+                assert_eq!(b & p.board().0, 0);
+                b | p.board().0
+            });
+            assert_eq!(cells.count_ones() % 10, 0);
+        }
+    }
+}
@@ -0,0 +1,294 @@
+extern crate pcf;
+
+use crate::board::{Board, BITBOARD_ROWS, HEIGHT, WIDTH};
+use crate::plan::{piece_cells, rotate_cells};
+use pcf::{Piece, Rotation};
+
+pub const ROTATIONS: [Rotation; 4] = [
+    Rotation::North,
+    Rotation::East,
+    Rotation::South,
+    Rotation::West,
+];
+
+/// Where the rotation centre of a new piece appears, just above the visible 20 rows.
+pub const SPAWN_X: i32 = 4;
+pub const SPAWN_Y: i32 = 20;
+
+// SRS offset tables, indexed by rotation. Kicks for a rotation are `from - to` of each
+// entry in order, which reproduces the usual SRS kick tables for pieces rotating around
+// the cell at (0, 0) of `plan::piece_cells`.
+const JLSTZ_OFFSETS: [[(i32, i32); 5]; 4] = [
+    [(0, 0), (0, 0), (0, 0), (0, 0), (0, 0)],
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+    [(0, 0), (0, 0), (0, 0), (0, 0), (0, 0)],
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+];
+const I_OFFSETS: [[(i32, i32); 5]; 4] = [
+    [(0, 0), (-1, 0), (2, 0), (-1, 0), (2, 0)],
+    [(-1, 0), (0, 0), (0, 0), (0, 1), (0, -2)],
+    [(-1, 1), (1, 1), (-2, 1), (1, 0), (-2, 0)],
+    [(0, 1), (0, 1), (0, 1), (0, -1), (0, 2)],
+];
+const O_OFFSETS: [[(i32, i32); 5]; 4] = [[(0, 0); 5], [(0, -1); 5], [(-1, -1); 5], [(-1, 0); 5]];
+
+pub fn rotation_index(rotation: Rotation) -> usize {
+    match rotation {
+        Rotation::North => 0,
+        Rotation::East => 1,
+        Rotation::South => 2,
+        Rotation::West => 3,
+    }
+}
+
+/// Occupancy of the field, bit `x` of row `y` in field coordinates.
+#[derive(Clone, Copy)]
+pub struct Field {
+    pub rows: [u16; HEIGHT],
+}
+
+impl Field {
+    pub fn empty() -> Field {
+        Field { rows: [0; HEIGHT] }
+    }
+
+    pub fn from_board(board: &Board) -> Field {
+        let mut field = Field::empty();
+        for (x, column) in board.columns.iter().enumerate() {
+            for (y, &cell) in column.iter().enumerate().take(HEIGHT) {
+                if cell != -1 {
+                    field.rows[y] |= 1 << x;
+                }
+            }
+        }
+        field
+    }
+
+    pub fn from_bitboard(board: pcf::BitBoard) -> Field {
+        let mut field = Field::empty();
+        for y in 0..BITBOARD_ROWS {
+            field.rows[y] = (board.0 >> (WIDTH * y) & ((1 << WIDTH) - 1)) as u16;
+        }
+        field
+    }
+
+    /// Walls and floor count as occupied, everything above the field as empty.
+    pub fn occupied(&self, x: i32, y: i32) -> bool {
+        if x < 0 || x >= WIDTH as i32 || y < 0 {
+            true
+        } else if y >= HEIGHT as i32 {
+            false
+        } else {
+            self.rows[y as usize] & (1 << x) != 0
+        }
+    }
+}
+
+/// A piece that has not been locked yet, positioned by its rotation centre.
+#[derive(Clone, Copy, Debug)]
+pub struct FallingPiece {
+    pub piece: Piece,
+    pub rotation: Rotation,
+    pub x: i32,
+    pub y: i32,
+}
+
+impl FallingPiece {
+    pub fn spawn(piece: Piece) -> FallingPiece {
+        FallingPiece {
+            piece,
+            rotation: Rotation::North,
+            x: SPAWN_X,
+            y: SPAWN_Y,
+        }
+    }
+
+    pub fn cells(&self) -> [(i32, i32); 4] {
+        let mut cells = piece_cells(self.piece);
+        rotate_cells(&mut cells, self.rotation);
+        let mut result = [(0, 0); 4];
+        for (r, &(x, y, _)) in result.iter_mut().zip(cells.iter()) {
+            *r = (x + self.x, y + self.y);
+        }
+        result
+    }
+
+    pub fn fits(&self, field: &Field) -> bool {
+        self.cells().iter().all(|&(x, y)| !field.occupied(x, y))
+    }
+
+    pub fn shift(&self, field: &Field, dx: i32) -> Option<FallingPiece> {
+        let moved = FallingPiece {
+            x: self.x + dx,
+            ..*self
+        };
+        if moved.fits(field) {
+            Some(moved)
+        } else {
+            None
+        }
+    }
+
+    /// Moves as far as possible in the direction of `dx`, like holding a key with DAS.
+    pub fn das(&self, field: &Field, dx: i32) -> Option<FallingPiece> {
+        let mut piece = self.shift(field, dx)?;
+        while let Some(moved) = piece.shift(field, dx) {
+            piece = moved;
+        }
+        Some(piece)
+    }
+
+    pub fn sonic_drop(&self, field: &Field) -> FallingPiece {
+        let mut piece = *self;
+        loop {
+            let moved = FallingPiece {
+                y: piece.y - 1,
+                ..piece
+            };
+            if !moved.fits(field) {
+                return piece;
+            }
+            piece = moved;
+        }
+    }
+
+    /// Rotates clockwise `turns` times, trying each SRS kick in order.
+    pub fn rotate(&self, field: &Field, turns: usize) -> Option<FallingPiece> {
+        let table = match self.piece {
+            Piece::I => &I_OFFSETS,
+            Piece::O => &O_OFFSETS,
+            _ => &JLSTZ_OFFSETS,
+        };
+        let from = rotation_index(self.rotation);
+        let to = (from + turns) % 4;
+        for (f, t) in table[from].iter().zip(table[to].iter()) {
+            let moved = FallingPiece {
+                rotation: ROTATIONS[to],
+                x: self.x + f.0 - t.0,
+                y: self.y + f.1 - t.1,
+                ..*self
+            };
+            if moved.fits(field) {
+                return Some(moved);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Kick tests of the SRS guideline for 0->R, R->0, R->2, 2->R, 2->L, L->2, L->0, 0->L.
+    const TRANSITIONS: [(usize, usize); 8] = [
+        (0, 1),
+        (1, 0),
+        (1, 2),
+        (2, 1),
+        (2, 3),
+        (3, 2),
+        (3, 0),
+        (0, 3),
+    ];
+    const JLSTZ_KICKS: [[(i32, i32); 5]; 8] = [
+        [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+        [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+        [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+        [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+        [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+        [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+        [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+        [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+    ];
+    const I_KICKS: [[(i32, i32); 5]; 8] = [
+        [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)],
+        [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)],
+        [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)],
+        [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)],
+        [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)],
+        [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)],
+        [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)],
+        [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)],
+    ];
+
+    /// The kicks an offset table gives, relative to the first one. The first kick only
+    /// moves the rotation centre of pieces whose centre is not a cell.
+    fn kicks(table: &[[(i32, i32); 5]; 4], from: usize, to: usize) -> Vec<(i32, i32)> {
+        let (fx, fy) = (
+            table[from][0].0 - table[to][0].0,
+            table[from][0].1 - table[to][0].1,
+        );
+        table[from]
+            .iter()
+            .zip(table[to].iter())
+            .map(|(f, t)| (f.0 - t.0 - fx, f.1 - t.1 - fy))
+            .collect()
+    }
+
+    #[test]
+    fn offset_tables_give_the_srs_kicks() {
+        for (&(from, to), (jlstz, i)) in TRANSITIONS
+            .iter()
+            .zip(JLSTZ_KICKS.iter().zip(I_KICKS.iter()))
+        {
+            assert_eq!(
+                kicks(&JLSTZ_OFFSETS, from, to),
+                jlstz.to_vec(),
+                "{}->{}",
+                from,
+                to
+            );
+            assert_eq!(kicks(&I_OFFSETS, from, to), i.to_vec(), "{}->{}", from, to);
+            assert_eq!(
+                kicks(&O_OFFSETS, from, to),
+                vec![(0, 0); 5],
+                "{}->{}",
+                from,
+                to
+            );
+        }
+    }
+
+    fn sorted(mut cells: [(i32, i32); 4]) -> [(i32, i32); 4] {
+        cells.sort();
+        cells
+    }
+
+    #[test]
+    fn rotations_in_open_space_match_the_guideline() {
+        let field = Field::empty();
+        let i = FallingPiece::spawn(Piece::I);
+        assert_eq!(sorted(i.cells()), [(3, 20), (4, 20), (5, 20), (6, 20)]);
+        let cw = i.rotate(&field, 1).unwrap();
+        assert_eq!(sorted(cw.cells()), [(5, 18), (5, 19), (5, 20), (5, 21)]);
+        let ccw = i.rotate(&field, 3).unwrap();
+        assert_eq!(sorted(ccw.cells()), [(4, 18), (4, 19), (4, 20), (4, 21)]);
+
+        let t = FallingPiece::spawn(Piece::T);
+        let cw = t.rotate(&field, 1).unwrap();
+        assert_eq!(sorted(cw.cells()), [(4, 19), (4, 20), (4, 21), (5, 20)]);
+
+        let o = FallingPiece::spawn(Piece::O);
+        for turns in 1..4 {
+            assert_eq!(
+                sorted(o.rotate(&field, turns).unwrap().cells()),
+                sorted(o.cells())
+            );
+        }
+    }
+
+    #[test]
+    fn rotation_against_the_wall_uses_the_next_kick() {
+        let field = Field::empty();
+        // A vertical T against the left wall has to kick right to rotate back to flat.
+        let t = FallingPiece {
+            piece: Piece::T,
+            rotation: Rotation::East,
+            x: 0,
+            y: 1,
+        };
+        let rotated = t.rotate(&field, 3).unwrap();
+        assert_eq!((rotated.x, rotated.y), (1, 1));
+    }
+}
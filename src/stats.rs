@@ -0,0 +1,225 @@
+extern crate serde_json;
+
+use crate::adherence::Adherence;
+use crate::board::Board;
+use crate::history::Move;
+use serde::{Deserialize, Serialize};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// PCs are only attempted on the bottom 4 rows; anything higher is a failed attempt.
+const MAX_PC_HEIGHT: usize = 4;
+
+#[derive(Clone, Debug)]
+struct Attempt {
+    started: Instant,
+    pieces: u32,
+    cleared: bool,
+}
+
+/// Practice statistics for one session.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Stats {
+    /// Unix time the session started at, in seconds.
+    pub started: u64,
+    pub attempts: u32,
+    pub successes: u32,
+    pub pieces_in_successes: u32,
+    pub success_time_ms: u64,
+    pub solves: u32,
+    pub solve_time_ms: u64,
+    pub no_solution: u32,
+    #[serde(default)]
+    pub cache_hits: u32,
+    #[serde(default)]
+    pub cache_misses: u32,
+    pub adherence: Adherence,
+    #[serde(skip)]
+    attempt: Option<Attempt>,
+}
+
+impl Stats {
+    pub fn new() -> Stats {
+        Stats {
+            started: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            attempts: 0,
+            successes: 0,
+            pieces_in_successes: 0,
+            success_time_ms: 0,
+            solves: 0,
+            solve_time_ms: 0,
+            no_solution: 0,
+            cache_hits: 0,
+            cache_misses: 0,
+            adherence: Adherence::new(),
+            attempt: None,
+        }
+    }
+
+    fn start_attempt(&mut self) {
+        self.attempts += 1;
+        self.attempt = Some(Attempt {
+            started: Instant::now(),
+            pieces: 0,
+            cleared: false,
+        });
+    }
+
+    pub fn record_lock(&mut self, m: &Move) {
+        if let Some(attempt) = &mut self.attempt {
+            attempt.pieces += 1;
+            attempt.cleared = m.lines_cleared() > 0;
+        }
+    }
+
+    /// Updates the current attempt from the board sent after every piece.
+    ///
+    /// An attempt starts on an empty field and succeeds when the field is emptied again by
+    /// a line clear. Returns true when a PC was just completed.
+    pub fn record_board(&mut self, board: &Board) -> bool {
+        let height = board.height();
+        match self.attempt.clone() {
+            Some(attempt) if height == 0 && attempt.pieces > 0 && attempt.cleared => {
+                self.successes += 1;
+                self.pieces_in_successes += attempt.pieces;
+                self.success_time_ms += attempt.started.elapsed().as_millis() as u64;
+                self.start_attempt();
+                return true;
+            }
+            Some(_) if height > MAX_PC_HEIGHT => self.attempt = None,
+            None if height == 0 => self.start_attempt(),
+            _ => {}
+        }
+        false
+    }
+
+    pub fn record_solve(&mut self, latency: Duration, found: bool) {
+        self.solves += 1;
+        self.solve_time_ms += latency.as_millis() as u64;
+        if !found {
+            self.no_solution += 1;
+        }
+    }
+
+    pub fn record_cache(&mut self, hit: bool) {
+        if hit {
+            self.cache_hits += 1;
+        } else {
+            self.cache_misses += 1;
+        }
+    }
+
+    /// Lines shown in the overlay HUD.
+    pub fn summary(&self) -> Vec<String> {
+        let mut lines = vec![format!("PC {}/{}", self.successes, self.attempts)];
+        if self.successes > 0 {
+            let successes = self.successes as f64;
+            lines.push(format!(
+                "{:.1} pieces/PC",
+                self.pieces_in_successes as f64 / successes
+            ));
+            lines.push(format!(
+                "{:.1}s/PC",
+                self.success_time_ms as f64 / 1000.0 / successes
+            ));
+        }
+        if self.solves > 0 {
+            lines.push(format!(
+                "solve {}ms",
+                self.solve_time_ms / self.solves as u64
+            ));
+        }
+        lines.push(format!("no PC {}", self.no_solution));
+        let lookups = self.cache_hits + self.cache_misses;
+        if lookups > 0 {
+            lines.push(format!(
+                "cache {:.0}%",
+                self.cache_hits as f64 * 100.0 / lookups as f64
+            ));
+        }
+        if let Some(p) = self.adherence.percentage() {
+            lines.push(format!("followed {:.0}%", p));
+        }
+        lines
+    }
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Stats::new()
+    }
+}
+
+/// All recorded sessions, with the current one last.
+pub struct StatsLog {
+    path: PathBuf,
+    previous: Vec<Stats>,
+    pub session: Stats,
+}
+
+impl StatsLog {
+    /// Loads earlier sessions from `path` and starts a new one. A missing file starts a
+    /// fresh log. A file that is not a valid log is moved to `<path>.bak` first, so saving
+    /// does not overwrite the records in it.
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<StatsLog> {
+        let path = path.as_ref().to_path_buf();
+        let previous = match std::fs::read(&path) {
+            Ok(data) => match serde_json::from_slice(&data) {
+                Ok(previous) => previous,
+                Err(e) => {
+                    let mut backup = path.clone().into_os_string();
+                    backup.push(".bak");
+                    std::fs::rename(&path, &backup)?;
+                    println!(
+                        "stats: {}: {}, moved it to {}",
+                        path.display(),
+                        e,
+                        Path::new(&backup).display()
+                    );
+                    vec![]
+                }
+            },
+            Err(e) if e.kind() == ErrorKind::NotFound => vec![],
+            Err(e) => return Err(e),
+        };
+        Ok(StatsLog {
+            path,
+            previous,
+            session: Stats::new(),
+        })
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        let mut sessions: Vec<&Stats> = self.previous.iter().collect();
+        sessions.push(&self.session);
+        let data = serde_json::to_vec_pretty(&sessions)?;
+        std::fs::write(&self.path, data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn corrupt_log_is_kept_as_a_backup() {
+        let dir = std::env::temp_dir().join(format!("pc_assist_stats_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("stats.json");
+        std::fs::write(&path, "not json").unwrap();
+
+        let log = StatsLog::open(&path).unwrap();
+        assert!(log.previous.is_empty());
+        assert_eq!(
+            std::fs::read_to_string(dir.join("stats.json.bak")).unwrap(),
+            "not json"
+        );
+        log.save().unwrap();
+        assert_eq!(StatsLog::open(&path).unwrap().previous.len(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,9 @@
+//! Helpers shared by the unit tests.
+
+use crate::solver;
+use pcf::{BitBoard, Piece, Placement};
+
+/// Every placement `pcf` considers for the first piece of `queue` on `board`.
+pub fn placements(board: BitBoard, queue: &[Piece]) -> Vec<Placement> {
+    solver::first_placements(queue, board, false, |_, _| true)
+}
@@ -0,0 +1,206 @@
+use crate::overlay::CELL_COLORS;
+use serde::{Deserialize, Serialize};
+
+/// Colours for the plan, one per piece in PPT order S Z J L T O I.
+pub type Colors = [[u8; 3]; 7];
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Palette {
+    /// Every piece white, as the overlay always drew its plan.
+    White,
+    /// The game's own piece colours.
+    Game,
+    /// The Okabe-Ito colours, which stay apart for the common kinds of colour blindness.
+    Colorblind,
+}
+
+impl Palette {
+    pub fn colors(self) -> Colors {
+        match self {
+            Palette::White => [[255; 3]; 7],
+            Palette::Game => {
+                let mut colors = [[0; 3]; 7];
+                for (color, cell) in colors.iter_mut().zip(CELL_COLORS.iter()) {
+                    color.copy_from_slice(&cell[..3]);
+                }
+                colors
+            }
+            Palette::Colorblind => [
+                [0, 158, 115],
+                [213, 94, 0],
+                [0, 114, 178],
+                [230, 159, 0],
+                [204, 121, 167],
+                [240, 228, 66],
+                [86, 180, 233],
+            ],
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PlanStyle {
+    /// The outline of each piece.
+    Outline,
+    /// The outline over a translucent fill.
+    Filled,
+}
+
+/// How the plan looks.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    pub palette: Palette,
+    /// Colours that replace the palette's.
+    pub colors: Option<Colors>,
+    /// Opacity of the next placement, from 0 to 1.
+    pub opacity: f32,
+    /// Opacity of the later placements when the full path is shown.
+    pub path_opacity: f32,
+    /// Opacity of the fill relative to the outline, for the filled style.
+    pub fill_opacity: f32,
+    pub style: PlanStyle,
+    /// Number the placements of the plan in the order they are played.
+    pub step_numbers: bool,
+    /// A directory with `plan.N.png`, `piece.N.png` or `fill.png` files that replace the
+    /// built in sprites when rendering frames to images. The window always draws the sprites
+    /// packed when building, tinted with the theme's colors.
+    pub sprites: Option<String>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            palette: Palette::White,
+            colors: None,
+            opacity: 1.0,
+            path_opacity: 0.45,
+            fill_opacity: 0.35,
+            style: PlanStyle::Outline,
+            step_numbers: false,
+            sprites: None,
+        }
+    }
+}
+
+fn alpha(opacity: f32) -> u8 {
+    (opacity.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+impl Theme {
+    /// The tint of a placement of `piece`, `next` for the next placement and false for
+    /// the later ones.
+    pub fn tint(&self, piece: u32, next: bool) -> [u8; 4] {
+        let colors = self.colors.unwrap_or_else(|| self.palette.colors());
+        let [r, g, b] = colors[piece as usize % colors.len()];
+        let opacity = if next {
+            self.opacity
+        } else {
+            self.opacity * self.path_opacity
+        };
+        [r, g, b, alpha(opacity)]
+    }
+
+    /// The tint of the fill under an outline drawn with `tint`.
+    pub fn fill(&self, tint: [u8; 4]) -> [u8; 4] {
+        let [r, g, b, a] = tint;
+        [r, g, b, alpha(a as f32 / 255.0 * self.fill_opacity)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn palettes_tint_each_piece() {
+        let theme = |palette| Theme {
+            palette,
+            ..Theme::default()
+        };
+        for piece in 0..7 {
+            assert_eq!(
+                theme(Palette::White).tint(piece, true),
+                [255, 255, 255, 255]
+            );
+        }
+        assert_eq!(theme(Palette::Game).tint(0, true), [89, 177, 1, 255]);
+        assert_eq!(theme(Palette::Game).tint(6, true), [15, 155, 215, 255]);
+        assert_eq!(
+            theme(Palette::Colorblind).tint(6, true),
+            [86, 180, 233, 255]
+        );
+    }
+
+    #[test]
+    fn custom_colors_replace_the_palette() {
+        let mut colors = [[0; 3]; 7];
+        colors[3] = [1, 2, 3];
+        let theme = Theme {
+            palette: Palette::Game,
+            colors: Some(colors),
+            ..Theme::default()
+        };
+        assert_eq!(theme.tint(3, true), [1, 2, 3, 255]);
+        assert_eq!(theme.tint(4, true), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn later_placements_are_fainter() {
+        let theme = Theme::default();
+        assert_eq!(theme.tint(0, false)[3], 115);
+
+        let theme = Theme {
+            opacity: 0.5,
+            path_opacity: 0.5,
+            ..Theme::default()
+        };
+        assert_eq!(theme.tint(0, true)[3], 128);
+        assert_eq!(theme.tint(0, false)[3], 64);
+
+        let theme = Theme {
+            opacity: 2.0,
+            path_opacity: -1.0,
+            ..Theme::default()
+        };
+        assert_eq!(theme.tint(0, true)[3], 255);
+        assert_eq!(theme.tint(0, false)[3], 0);
+    }
+
+    #[test]
+    fn fill_keeps_the_colour_and_scales_the_opacity() {
+        let theme = Theme::default();
+        assert_eq!(theme.fill([10, 20, 30, 255]), [10, 20, 30, 89]);
+        assert_eq!(theme.fill([10, 20, 30, 115]), [10, 20, 30, 40]);
+        assert_eq!(theme.fill([10, 20, 30, 0]), [10, 20, 30, 0]);
+    }
+
+    #[test]
+    fn deserializes_with_defaults_for_missing_options() {
+        let theme: Theme =
+            serde_json::from_str(r#"{"palette": "colorblind", "style": "filled"}"#).unwrap();
+        assert_eq!(theme.palette, Palette::Colorblind);
+        assert_eq!(theme.style, PlanStyle::Filled);
+        assert_eq!(theme.tint(0, true), [0, 158, 115, 255]);
+        assert_eq!(theme.tint(0, false), [0, 158, 115, 115]);
+        assert_eq!(theme.colors, None);
+        assert_eq!(theme.sprites, None);
+
+        let theme: Theme = serde_json::from_str("{}").unwrap();
+        assert_eq!(theme.palette, Palette::White);
+        assert_eq!(theme.style, PlanStyle::Outline);
+        assert!(!theme.step_numbers);
+
+        let mut colors = [[0; 3]; 7];
+        colors[0] = [1, 2, 3];
+        let json = serde_json::json!({ "colors": colors, "sprites": "my sprites" });
+        let theme: Theme = serde_json::from_value(json).unwrap();
+        assert_eq!(theme.tint(0, true), [1, 2, 3, 255]);
+        assert_eq!(theme.sprites.as_deref(), Some("my sprites"));
+
+        assert!(serde_json::from_str::<Theme>(r#"{"palette": "neon"}"#).is_err());
+        assert!(serde_json::from_str::<Theme>(r#"{"colors": [[1, 2, 3]]}"#).is_err());
+    }
+}
@@ -0,0 +1,118 @@
+use crate::board::{Board, BoardEvent};
+use crate::history::History;
+use std::collections::HashSet;
+
+/// What one snapshot changed.
+pub struct Update {
+    /// A new game started. The game may have moved the player's data, so the reader
+    /// should look up the player again.
+    pub reset: bool,
+    pub events: Vec<BoardEvent>,
+}
+
+/// Turns snapshots of the game into `BoardEvent`s: the moves played, and the board with
+/// one more queue piece when the rest of the bag is known.
+pub struct Tracker {
+    prev: Board,
+    pub history: History,
+    /// Pieces of the current bag that have not been seen in the queue yet.
+    mino_set: HashSet<u32>,
+    can_prediction: bool,
+    need_reset: bool,
+    resetted: bool,
+}
+
+impl Tracker {
+    /// `in_game` is whether a piece is already falling, in which case the bag position is
+    /// unknown until the next game starts.
+    pub fn new(in_game: bool) -> Tracker {
+        Tracker {
+            prev: Board {
+                columns: vec![],
+                current_piece: None,
+                hold: None,
+                next_pieces: vec![],
+            },
+            history: History::new(),
+            mino_set: HashSet::new(),
+            can_prediction: !in_game,
+            need_reset: !in_game,
+            resetted: in_game,
+        }
+    }
+
+    pub fn observe(&mut self, board: Board) -> Update {
+        let mut update = Update {
+            reset: false,
+            events: vec![],
+        };
+        if board.current_piece.is_none() && !self.need_reset && !self.resetted {
+            self.can_prediction = true;
+            self.need_reset = true;
+        }
+        if board.current_piece.is_none() && self.need_reset && !self.resetted {
+            let next: HashSet<_> = board.next_pieces.iter().copied().collect();
+            self.mino_set = (0..7).filter(|i| !next.contains(i)).collect();
+            self.history.clear();
+            self.resetted = true;
+            self.need_reset = false;
+            update.reset = true;
+        }
+        if self.mino_set.is_empty() {
+            self.mino_set = (0..7).collect();
+        }
+
+        let mut board = board;
+        let diff = board.diff(&self.prev);
+        // The current piece alone can look unchanged when the next piece has the same type.
+        if !diff.piece_locked
+            && !diff.queue_advanced
+            && !diff.hold_used
+            && diff.garbage_received == 0
+        {
+            return update;
+        }
+        if let Some(m) = self.history.update(&self.prev, &board, &diff) {
+            update.events.push(BoardEvent::Lock(m));
+        }
+        self.prev = board.clone();
+
+        self.resetted = false;
+
+        if self.can_prediction {
+            if diff.queue_advanced {
+                if let Some(i) = board.next_pieces.last() {
+                    self.mino_set.retain(|&j| j != *i);
+                }
+            }
+
+            if self.mino_set.len() == 1 {
+                for i in self.mino_set.iter() {
+                    board.next_pieces.push(*i);
+                }
+            }
+        }
+
+        update.events.push(BoardEvent::Continue(board));
+        update
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lock_of_a_piece_followed_by_the_same_piece_is_reported() {
+        let mut tracker = Tracker::new(false);
+        tracker.observe("current: S\nnext: SZOJL\n".parse().unwrap());
+        let update = tracker.observe(
+            "current: S\nnext: ZOJLT\n.SS.......\nSS........\n"
+                .parse()
+                .unwrap(),
+        );
+        assert!(matches!(update.events[0], BoardEvent::Lock(_)));
+        assert!(matches!(update.events[1], BoardEvent::Continue(_)));
+        assert_eq!(tracker.history.moves.len(), 1);
+    }
+}
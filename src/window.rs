@@ -1,13 +1,116 @@
-use crate::plan::Cells;
+use crate::Control;
 use game_util::prelude::*;
 use game_util::GameloopCommand;
 use glutin::*;
-use std::sync::mpsc::Receiver;
-use std::sync::Arc;
+use pc_assist::config::{Config, WindowMode};
+use pc_assist::finesse::Input;
+use pc_assist::hotkeys::{Action, Bindings, Hotkeys};
+use pc_assist::layout::Layout;
+use pc_assist::overlay::{Align, Overlay, OverlayEvent, OverlayRenderer, Sprite};
+#[cfg(not(windows))]
+use std::collections::HashSet;
+use std::sync::mpsc::{Receiver, Sender};
 
 #[cfg(windows)]
 extern crate winapi;
 
+/// How much one notch of the mouse wheel scales the field while calibrating.
+const CALIBRATION_ZOOM: f32 = 0.005;
+
+/// Where the game's client area is on screen and whether the overlay should be shown.
+#[cfg(windows)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct GameWindow {
+    left: i32,
+    top: i32,
+    width: i32,
+    height: i32,
+    /// The game is minimized or another window than the game and the overlay is in front.
+    hidden: bool,
+}
+
+#[cfg(windows)]
+impl GameWindow {
+    /// The current state of the game window `hwnd`, `None` once it is gone.
+    fn find(hwnd: HWND, overlay: HWND) -> Option<GameWindow> {
+        if unsafe { IsWindow(hwnd) } == FALSE {
+            return None;
+        }
+        let client = get_client_rect_by_window_handle(hwnd).ok()?;
+        let mut origin = POINT { x: 0, y: 0 };
+        if unsafe { ClientToScreen(hwnd, &mut origin) } == FALSE {
+            return None;
+        }
+        let foreground = get_foreground_window();
+        Some(GameWindow {
+            left: origin.x,
+            top: origin.y,
+            width: client.right - client.left,
+            height: client.bottom - client.top,
+            hidden: unsafe { IsIconic(hwnd) } != FALSE
+                || (foreground != hwnd && foreground != overlay),
+        })
+    }
+
+    fn same_area(&self, other: &GameWindow) -> bool {
+        (self.left, self.top, self.width, self.height)
+            == (other.left, other.top, other.width, other.height)
+    }
+}
+
+/// Makes `hwnd` a layered window that mouse clicks and focus pass through to the game, or
+/// an ordinary one that can be dragged while calibrating.
+#[cfg(windows)]
+fn set_click_through(hwnd: HWND, click_through: bool) {
+    let passthrough = (WS_EX_TRANSPARENT | WS_EX_NOACTIVATE) as LONG_PTR;
+    unsafe {
+        let style =
+            GetWindowLongPtrW(hwnd, GWL_EXSTYLE) | (WS_EX_LAYERED | WS_EX_TOOLWINDOW) as LONG_PTR;
+        let style = if click_through {
+            style | passthrough
+        } else {
+            style & !passthrough
+        };
+        SetWindowLongPtrW(hwnd, GWL_EXSTYLE, style);
+        // A layered window is invisible until its attributes are set; full alpha keeps the
+        // per-pixel transparency of the GL surface.
+        SetLayeredWindowAttributes(hwnd, 0, 255, LWA_ALPHA);
+    }
+}
+
+/// Draws with the window's GL sprite batch and text renderer.
+struct GlRenderer<'a> {
+    sprite_batch: &'a mut game_util::SpriteBatch,
+    text: &'a mut game_util::TextRenderer,
+    sprites: &'a sprites::Sprites,
+    layout: Layout,
+}
+
+impl OverlayRenderer for GlRenderer<'_> {
+    fn sprite(&mut self, sprite: Sprite, x: f32, y: f32, cell: f32, tint: [u8; 4]) {
+        self.sprite_batch.pixels_per_unit = sprite.pixels_per_cell() / cell;
+        let sprite = match sprite {
+            Sprite::Plan(i) => &self.sprites.plan[i],
+            Sprite::Piece(i) => &self.sprites.piece[i],
+            Sprite::Fill => &self.sprites.fill,
+        };
+        self.sprite_batch.draw(sprite, point2(x, y), tint);
+    }
+
+    fn text(&mut self, text: &str, x: f32, y: f32, align: Align, color: [u8; 4], size: f32) {
+        let align = match align {
+            Align::Left => game_util::Alignment::Left,
+            Align::Center => game_util::Alignment::Center,
+            Align::Right => game_util::Alignment::Right,
+        };
+        // The text renderer works in logical pixels from the bottom left corner.
+        let (x, y) = self.layout.picture_point(x, y);
+        let y = self.layout.client_height - y;
+        let size = size * self.layout.pixels_per_cell();
+        self.text.draw_text(text, x, y, align, color, size, 0);
+    }
+}
+
 pub struct Game {
     context: WindowedContext<PossiblyCurrent>,
     lsize: dpi::LogicalSize,
@@ -17,20 +120,49 @@ pub struct Game {
     text: game_util::TextRenderer,
     sprite_batch: game_util::SpriteBatch,
     sprites: sprites::Sprites,
-    recv: Receiver<Arc<Option<Cells>>>,
-    cells: Arc<Option<Cells>>,
+    recv: Receiver<OverlayEvent>,
+    overlay: Overlay,
+    config_path: String,
+    practice: bool,
+    /// Where keys for the simulator go, when playing in it.
+    inputs: Option<Sender<Input>>,
+    hotkeys: Hotkeys,
+    /// Where hotkeys that change what gets solved go.
+    controls: Sender<Control>,
+    cursor: (f64, f64),
+    drag_from: Option<(f64, f64)>,
+    /// The game being followed, `None` in practice mode.
+    #[cfg(windows)]
+    attached: Option<Attached>,
+    /// Windows virtual-key codes of the keys held down in this window.
+    #[cfg(not(windows))]
+    keys_down: HashSet<u8>,
+}
+
+/// The game's window and the overlay's own, for an overlay over the game.
+#[cfg(windows)]
+struct Attached {
     hwnd: HWND,
+    own_hwnd: HWND,
+    /// The game window at the last update, `None` before it was found.
+    game_window: Option<GameWindow>,
 }
 
 impl Game {
     pub fn new(
         context: WindowedContext<PossiblyCurrent>,
         lsize: dpi::LogicalSize,
-        recv: Receiver<Arc<Option<Cells>>>,
-        pid: DWORD,
+        recv: Receiver<OverlayEvent>,
+        game_pid: Option<u32>,
+        config: &Config,
+        inputs: Option<Sender<Input>>,
+        controls: Sender<Control>,
     ) -> Game {
         let (sprites, sprite_sheet) = sprites::Sprites::load();
-        let hwnd = get_window_handle_by_process_id(pid);
+        let hotkeys = Hotkeys::new(&config.hotkeys).unwrap_or_else(|e| {
+            println!("hotkeys: {}", e);
+            Hotkeys::new(&Bindings::default()).unwrap()
+        });
         let mut game = Game {
             context,
             lsize,
@@ -47,45 +179,214 @@ impl Game {
                 .unwrap()]));
                 t
             },
-            sprite_batch: {
-                let mut sprite_batch =
-                    game_util::SpriteBatch::new(game_util::sprite_shader(), sprite_sheet);
-                sprite_batch.pixels_per_unit = 83.0;
-                sprite_batch
-            },
+            sprite_batch: game_util::SpriteBatch::new(game_util::sprite_shader(), sprite_sheet),
             sprites: sprites,
             recv: recv,
-            cells: Arc::new(None),
-            hwnd,
+            overlay: Overlay::new(config.field),
+            config_path: config.path.clone(),
+            practice: config.window == WindowMode::Practice,
+            inputs,
+            hotkeys,
+            controls,
+            cursor: (0.0, 0.0),
+            drag_from: None,
+            #[cfg(windows)]
+            attached: None,
+            #[cfg(not(windows))]
+            keys_down: HashSet::new(),
         };
-        game.adjust_size();
-        game.adjust_position();
+        game.overlay.theme = config.theme.clone();
+        game.overlay.show_board = game.practice;
+        #[cfg(windows)]
+        {
+            if let (false, Some(pid)) = (game.practice, game_pid) {
+                use glutin::os::windows::WindowExt;
+                let own_hwnd = game.context.window().get_hwnd() as HWND;
+                set_click_through(own_hwnd, true);
+                game.attached = Some(Attached {
+                    hwnd: get_window_handle_by_process_id(pid),
+                    own_hwnd,
+                    game_window: None,
+                });
+                game.follow_game();
+            }
+        }
+        #[cfg(not(windows))]
+        let _ = game_pid;
         game
     }
 
-    pub fn adjust_size(&mut self) {
-        let client_rect = get_client_rect_by_window_handle(self.hwnd).unwrap();
-        let new_size =
-            glutin::dpi::LogicalSize::new(client_rect.right as f64, client_rect.bottom as f64);
+    /// Keeps the overlay over the game's client area, and hidden while the game cannot be
+    /// seen. Only changes are applied, so the window does not flicker.
+    #[cfg(windows)]
+    fn follow_game(&mut self) {
+        let attached = match &mut self.attached {
+            Some(attached) => attached,
+            None => return,
+        };
+        let now = match GameWindow::find(attached.hwnd, attached.own_hwnd) {
+            Some(now) => now,
+            None => return,
+        };
+        let before = attached.game_window.replace(now);
+        let window = self.context.window();
+        if before.map(|b| b.hidden) != Some(now.hidden) {
+            if now.hidden {
+                window.hide();
+            } else {
+                window.show();
+            }
+        }
+        if before.map_or(true, |b| !b.same_area(&now)) {
+            // The game's window is measured in physical pixels.
+            let hidpi = window.get_hidpi_factor();
+            let size = dpi::PhysicalSize::new(now.width as f64, now.height as f64);
+            let position = dpi::PhysicalPosition::new(now.left as f64, now.top as f64);
+            window.set_position(position.to_logical(hidpi));
+            window.set_inner_size(size.to_logical(hidpi));
+            self.context.resize(size);
+        }
+    }
+
+    fn layout(&self) -> Layout {
+        Layout::new(self.lsize.width as f32, self.lsize.height as f32)
+    }
+
+    /// Writes the calibrated field placement to the config file, keeping its other options.
+    fn save_field(&self) -> std::io::Result<()> {
+        Config::save_field(&self.config_path, self.overlay.field)
+    }
+
+    /// Shows the calibration grid and lets the overlay take the mouse and keyboard until
+    /// calibration ends.
+    fn start_calibration(&mut self) {
+        if self.overlay.calibration.is_some() {
+            return;
+        }
+        self.overlay.calibration = Some(self.overlay.field);
+        #[cfg(windows)]
+        {
+            if let Some(attached) = &self.attached {
+                set_click_through(attached.own_hwnd, false);
+                unsafe { SetForegroundWindow(attached.own_hwnd) };
+            }
+        }
+    }
+
+    fn end_calibration(&mut self) {
+        self.overlay.calibration = None;
+        #[cfg(windows)]
+        {
+            if let Some(attached) = &self.attached {
+                set_click_through(attached.own_hwnd, true);
+                unsafe { SetForegroundWindow(attached.hwnd) };
+            }
+        }
+    }
+
+    fn calibration_key(&mut self, key: VirtualKeyCode) {
+        match (key, self.overlay.calibration) {
+            (VirtualKeyCode::Return, Some(_)) => {
+                self.end_calibration();
+                if let Err(e) = self.save_field() {
+                    println!("could not save calibration: {}", e);
+                }
+            }
+            (VirtualKeyCode::Escape, Some(before)) => {
+                self.overlay.field = before;
+                self.end_calibration();
+            }
+            _ => {}
+        }
+        self.drag_from = None;
+    }
 
-        self.context
-            .resize(new_size.to_physical(self.context.window().get_hidpi_factor()));
-        self.context.window().set_inner_size(new_size);
+    /// Reads the hotkeys from the keyboard state, so they work while the game has focus.
+    #[cfg(windows)]
+    fn read_hotkeys(&mut self) -> Vec<Action> {
+        self.hotkeys.poll(|key| unsafe {
+            winapi::um::winuser::GetAsyncKeyState(key as i32) as u16 & 0x8000 != 0
+        })
     }
 
-    pub fn adjust_position(&mut self) {
-        let pos_rect = get_window_rect_by_window_handle(self.hwnd).unwrap();
-        self.context
-            .window()
-            .set_position(glutin::dpi::LogicalPosition::new(
-                pos_rect.left as f64,
-                pos_rect.top as f64,
-            ));
+    /// Reads the hotkeys from the keys held in this window, the only keyboard state there
+    /// is without Windows.
+    #[cfg(not(windows))]
+    fn read_hotkeys(&mut self) -> Vec<Action> {
+        let keys_down = &self.keys_down;
+        self.hotkeys.poll(|key| keys_down.contains(&key))
     }
 
-    pub fn ppt_is_active(&self) -> bool {
-        get_foreground_window() == self.hwnd
+    fn poll_hotkeys(&mut self) {
+        for action in self.read_hotkeys() {
+            match action {
+                Action::Calibrate => self.start_calibration(),
+                Action::NextSolution => {
+                    self.controls.send(Control::NextSolution).ok();
+                }
+                Action::PauseSolver => {
+                    self.controls.send(Control::PauseSolver).ok();
+                }
+                Action::ToggleOverlay | Action::ToggleFullPath => {
+                    self.overlay.hotkey(action);
+                }
+            }
+        }
+    }
+}
+
+/// Keys for playing in the simulator. Key repeat stands in for DAS.
+fn simulator_input(key: VirtualKeyCode) -> Option<Input> {
+    Some(match key {
+        VirtualKeyCode::Left => Input::Left,
+        VirtualKeyCode::Right => Input::Right,
+        VirtualKeyCode::Up | VirtualKeyCode::X => Input::Cw,
+        VirtualKeyCode::Z => Input::Ccw,
+        VirtualKeyCode::A => Input::Rotate180,
+        VirtualKeyCode::Down => Input::SoftDrop,
+        VirtualKeyCode::Space => Input::HardDrop,
+        VirtualKeyCode::C | VirtualKeyCode::LShift => Input::Hold,
+        _ => return None,
+    })
+}
+
+/// The Windows virtual-key code of `key`, for the keys hotkeys can use.
+#[cfg(not(windows))]
+fn virtual_key_code(key: VirtualKeyCode) -> Option<u8> {
+    use glutin::VirtualKeyCode::*;
+
+    const LETTERS: [VirtualKeyCode; 26] = [
+        A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    ];
+    const DIGITS: [VirtualKeyCode; 10] =
+        [Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9];
+    const FUNCTION: [VirtualKeyCode; 24] = [
+        F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12, F13, F14, F15, F16, F17, F18, F19, F20,
+        F21, F22, F23, F24,
+    ];
+    if let Some(i) = LETTERS.iter().position(|&k| k == key) {
+        return Some(b'A' + i as u8);
+    }
+    if let Some(i) = DIGITS.iter().position(|&k| k == key) {
+        return Some(b'0' + i as u8);
+    }
+    if let Some(i) = FUNCTION.iter().position(|&k| k == key) {
+        return Some(0x70 + i as u8);
     }
+    Some(match key {
+        LShift | RShift => 0x10,
+        LControl | RControl => 0x11,
+        LAlt | RAlt => 0x12,
+        Pause => 0x13,
+        Space => 0x20,
+        PageUp => 0x21,
+        PageDown => 0x22,
+        End => 0x23,
+        Home => 0x24,
+        Insert => 0x2D,
+        Delete => 0x2E,
+        _ => return None,
+    })
 }
 
 include!(concat!(env!("OUT_DIR"), "/sprites.rs"));
@@ -98,12 +399,12 @@ impl game_util::Game for Game {
         // self.context
         //     .window()
         //     .set_position(dpi::LogicalPosition::new(100.0, 100.0));
-        if let Some(s) = self.recv.try_recv().ok() {
-            self.cells = s;
+        while let Ok(event) = self.recv.try_recv() {
+            self.overlay.handle(event);
         }
-        self.context
-            .window()
-            .set_always_on_top(self.ppt_is_active());
+        self.poll_hotkeys();
+        #[cfg(windows)]
+        self.follow_game();
         GameloopCommand::Continue
     }
 
@@ -111,26 +412,14 @@ impl game_util::Game for Game {
         let dpi = self.context.window().get_hidpi_factor();
         self.text.dpi = dpi as f32;
         self.text.screen_size = (self.lsize.width as f32, self.lsize.height as f32);
+        let layout = self.layout();
 
-        // for y in (0..20).rev() {
-        //     for x in (0..10).rev() {
-        //         self.sprite_batch.draw(
-        //             &self.sprites.plan[0],
-        //             point2(x as f32 + 9.1, y as f32 + 6.1),
-        //             [255; 4],
-        //         );
-        //     }
-        // }
-
-        if let Some(cells) = *self.cells {
-            for &(x, y, d) in &cells {
-                self.sprite_batch.draw(
-                    &self.sprites.plan[d.to_bits() as usize],
-                    point2(x as f32 + 9.1, y as f32 + 6.1),
-                    [255; 4],
-                );
-            }
-        }
+        self.overlay.draw(&mut GlRenderer {
+            sprite_batch: &mut self.sprite_batch,
+            text: &mut self.text,
+            sprites: &self.sprites,
+            layout,
+        });
 
         let (width, height): (u32, _) = self.lsize.to_physical(dpi).into();
         let (width, height) = (width as i32, height as i32);
@@ -138,7 +427,11 @@ impl game_util::Game for Game {
         unsafe {
             gl::Viewport(0, 0, width, height);
 
-            gl::ClearColor(0.0, 0.0, 0.0, 0.0);
+            if self.practice {
+                gl::ClearColor(0.1, 0.1, 0.1, 1.0);
+            } else {
+                gl::ClearColor(0.0, 0.0, 0.0, 0.0);
+            }
             gl::Clear(gl::COLOR_BUFFER_BIT);
 
             gl::Enable(gl::BLEND);
@@ -146,8 +439,9 @@ impl game_util::Game for Game {
         }
 
         self.text.render();
+        let (left, right, bottom, top) = layout.projection();
         self.sprite_batch
-            .render(Transform3D::ortho(0.0, 53.5, 0.0, 30.05, -1.0, 1.0));
+            .render(Transform3D::ortho(left, right, bottom, top, -1.0, 1.0));
 
         self.context.swap_buffers().unwrap();
     }
@@ -161,14 +455,61 @@ impl game_util::Game for Game {
                 self.context.resize(psize);
                 self.lsize = new_size;
             }
-            WindowEvent::MouseInput {
-                device_id: _device_id,
-                state: _element_state,
-                button: _mouse_button,
-                modifiers: _modifiers_state,
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state,
+                        virtual_keycode: Some(key),
+                        ..
+                    },
+                ..
             } => {
-                self.adjust_position();
-                self.adjust_size();
+                #[cfg(not(windows))]
+                {
+                    if let Some(code) = virtual_key_code(key) {
+                        match state {
+                            ElementState::Pressed => self.keys_down.insert(code),
+                            ElementState::Released => self.keys_down.remove(&code),
+                        };
+                    }
+                }
+                if state == ElementState::Pressed {
+                    if let (Some(inputs), Some(input)) = (&self.inputs, simulator_input(key)) {
+                        inputs.send(input).ok();
+                    }
+                    self.calibration_key(key);
+                }
+            }
+            WindowEvent::Focused(false) => {
+                #[cfg(not(windows))]
+                self.keys_down.clear();
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor = (position.x, position.y);
+                if let Some((x, y)) = self.drag_from {
+                    let ppc = self.layout().pixels_per_cell();
+                    self.overlay.field.origin.0 += (position.x - x) as f32 / ppc;
+                    // The picture's y goes up.
+                    self.overlay.field.origin.1 -= (position.y - y) as f32 / ppc;
+                    self.drag_from = Some(self.cursor);
+                }
+            }
+            WindowEvent::MouseWheel { delta, .. } if self.overlay.calibration.is_some() => {
+                let notches = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => y,
+                    MouseScrollDelta::PixelDelta(p) => p.y as f32 / 20.0,
+                };
+                self.overlay.field.cell *= 1.0 + notches * CALIBRATION_ZOOM;
+            }
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Left,
+                ..
+            } if self.overlay.calibration.is_some() => {
+                self.drag_from = match state {
+                    ElementState::Pressed => Some(self.cursor),
+                    ElementState::Released => None,
+                };
             }
             _ => {}
         }
@@ -176,21 +517,32 @@ impl game_util::Game for Game {
     }
 }
 
+#[cfg(windows)]
 use std::mem;
+#[cfg(windows)]
 use std::os::raw::c_void;
+#[cfg(windows)]
+use winapi::shared::basetsd::LONG_PTR;
+#[cfg(windows)]
 use winapi::shared::minwindef::*;
+#[cfg(windows)]
 use winapi::shared::windef::*;
+#[cfg(windows)]
 use winapi::um::winuser::{
-    EnumWindows, GetClientRect, GetWindow, GetWindowRect, GetWindowThreadProcessId,
-    IsWindowVisible, GW_OWNER,
+    ClientToScreen, EnumWindows, GetClientRect, GetWindow, GetWindowLongPtrW,
+    GetWindowThreadProcessId, IsIconic, IsWindow, IsWindowVisible, SetForegroundWindow,
+    SetLayeredWindowAttributes, SetWindowLongPtrW, GWL_EXSTYLE, GW_OWNER, LWA_ALPHA, WS_EX_LAYERED,
+    WS_EX_NOACTIVATE, WS_EX_TOOLWINDOW, WS_EX_TRANSPARENT,
 };
 
+#[cfg(windows)]
 #[derive(Debug)]
 struct HandleData {
     process_id: DWORD,
     window_handle: HWND,
 }
 
+#[cfg(windows)]
 pub fn get_client_rect_by_window_handle(hwnd: HWND) -> std::io::Result<RECT> {
     let mut rect = RECT {
         left: 0,
@@ -206,21 +558,7 @@ pub fn get_client_rect_by_window_handle(hwnd: HWND) -> std::io::Result<RECT> {
     }
 }
 
-pub fn get_window_rect_by_window_handle(hwnd: HWND) -> std::io::Result<RECT> {
-    let mut rect = RECT {
-        left: 0,
-        top: 0,
-        right: 0,
-        bottom: 0,
-    };
-
-    if unsafe { GetWindowRect(hwnd, &mut rect) } == FALSE {
-        Err(std::io::Error::last_os_error())
-    } else {
-        Ok(rect)
-    }
-}
-
+#[cfg(windows)]
 fn get_window_handle_by_process_id(pid: DWORD) -> HWND {
     let handle_data = HandleData {
         process_id: pid,
@@ -237,6 +575,7 @@ fn get_window_handle_by_process_id(pid: DWORD) -> HWND {
     handle_data.window_handle
 }
 
+#[cfg(windows)]
 unsafe extern "system" fn enum_proc(hwnd: HWND, l_param: LPARAM) -> BOOL {
     let handle_data: &mut HandleData = { mem::transmute(l_param as *mut c_void) };
     let mut process_id: DWORD = 0;
@@ -248,10 +587,12 @@ unsafe extern "system" fn enum_proc(hwnd: HWND, l_param: LPARAM) -> BOOL {
     FALSE
 }
 
+#[cfg(windows)]
 unsafe fn is_main_window(hwnd: HWND) -> bool {
     return GetWindow(hwnd, GW_OWNER) == 0 as HWND && IsWindowVisible(hwnd) != 0;
 }
 
+#[cfg(windows)]
 fn get_foreground_window() -> HWND {
     unsafe { winapi::um::winuser::GetForegroundWindow() }
 }
@@ -0,0 +1,82 @@
+//! Plays seeded games in the simulator through the same tracker as the game, checking the
+//! queue it reports and when a PC is first suggested.
+
+use pc_assist::config::SolverConfig;
+use pc_assist::finesse::Input::{self, *};
+use pc_assist::harness::{self, Report, Script};
+use pc_assist::sim::Simulator;
+
+/// Without a time limit the suggestions do not depend on how fast the machine is.
+fn config() -> SolverConfig {
+    SolverConfig {
+        timeout_ms: None,
+        ..SolverConfig::default()
+    }
+}
+
+fn play(seed: u64, pieces: usize, script: &Script) -> Report {
+    harness::play(Simulator::new(seed), pieces, script, &config())
+}
+
+/// A perfect clear of the first 10 pieces dealt with seed 5.
+fn seed_5_opener() -> Vec<Vec<Input>> {
+    vec![
+        vec![DasLeft, HardDrop],
+        vec![HardDrop],
+        vec![Hold, DasRight, HardDrop],
+        vec![Right, Right, HardDrop],
+        vec![DasLeft, Cw, HardDrop],
+        vec![HardDrop],
+        vec![HardDrop],
+        vec![Ccw, DasRight, HardDrop],
+        vec![Cw, DasLeft, HardDrop],
+        vec![Right, Right, HardDrop],
+    ]
+}
+
+#[test]
+fn a_pc_is_suggested_once_the_queue_shows_the_rest_of_the_opener() {
+    let report = play(5, 10, &Script::Inputs(seed_5_opener()));
+
+    assert_eq!(report.pieces, 10);
+    assert_eq!(report.perfect_clears, 1);
+    assert_eq!(report.queue_errors, Vec::<usize>::new());
+    // After 4 pieces, the current piece, hold and next queue hold the other 6.
+    let first = report.first_suggestion.expect("no PC suggested");
+    assert!(first <= 4, "first PC suggested after {} pieces", first);
+}
+
+#[test]
+fn following_suggestions_keeps_the_queue_in_step() {
+    for seed in 0..8 {
+        let report = play(seed, 20, &Script::FollowSuggestions);
+        assert!(report.pieces > 0, "seed {}: {:?}", seed, report);
+        assert_eq!(
+            report.queue_errors,
+            Vec::<usize>::new(),
+            "seed {}: {:?}",
+            seed,
+            report
+        );
+    }
+}
+
+#[test]
+fn holding_every_piece_keeps_the_queue_in_step() {
+    let sides = [DasLeft, DasRight, Left, Right];
+    let inputs = (0..20)
+        .map(|i| vec![Hold, sides[i % sides.len()], HardDrop])
+        .collect();
+    let script = Script::Inputs(inputs);
+    for seed in 0..8 {
+        let report = play(seed, 20, &script);
+        assert!(report.pieces > 1, "seed {}: {:?}", seed, report);
+        assert_eq!(
+            report.queue_errors,
+            Vec::<usize>::new(),
+            "seed {}: {:?}",
+            seed,
+            report
+        );
+    }
+}
@@ -0,0 +1,139 @@
+//! Compares frames drawn by `Renderer` with PNGs checked in under `tests/fixtures/render`.
+//! Run with `UPDATE_GOLDEN=1` to write the current frames as the new fixtures.
+
+use pc_assist::board::Board;
+use pc_assist::layout::FieldPlacement;
+use pc_assist::overlay::{Overlay, OverlayEvent};
+use pc_assist::plan::Step;
+use pc_assist::render::Renderer;
+use pc_assist::theme::{Palette, PlanStyle, Theme};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Largest difference allowed in any channel, for changes in how images are resized.
+const TOLERANCE: u8 = 2;
+
+const BOARD: &str = "hold: T\ncurrent: I\nnext: OLJSZ\nLL........\nLJJJ....ZZ\n";
+
+fn plan() -> Vec<Step> {
+    vec![
+        Step {
+            piece: 5,
+            cells: [(4, 0), (5, 0), (4, 1), (5, 1)],
+        },
+        Step {
+            piece: 3,
+            cells: [(6, 0), (7, 0), (6, 1), (6, 2)],
+        },
+        Step {
+            piece: 6,
+            cells: [(7, 1), (7, 2), (7, 3), (7, 4)],
+        },
+    ]
+}
+
+fn check(name: &str, theme: Theme, full_path: bool) {
+    let mut renderer = Renderer::new(320, 180, FieldPlacement::PLAYER_1);
+    renderer.set_theme(theme).unwrap();
+    let board: Board = BOARD.parse().unwrap();
+    let frame = renderer.render(&board, &plan(), full_path);
+
+    let path: PathBuf = [
+        env!("CARGO_MANIFEST_DIR"),
+        "tests",
+        "fixtures",
+        "render",
+        name,
+    ]
+    .iter()
+    .collect();
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        frame.save(&path).unwrap();
+        return;
+    }
+    let golden = image::open(&path)
+        .unwrap_or_else(|e| panic!("{}: {}", path.display(), e))
+        .to_rgba8();
+    assert_eq!(frame.dimensions(), golden.dimensions(), "{}", name);
+    for (x, y, pixel) in frame.enumerate_pixels() {
+        let expected = golden.get_pixel(x, y);
+        let off = pixel
+            .0
+            .iter()
+            .zip(expected.0.iter())
+            .any(|(&a, &b)| (a as i16 - b as i16).abs() > TOLERANCE as i16);
+        assert!(
+            !off,
+            "{}: pixel ({}, {}) is {:?}, expected {:?}",
+            name, x, y, pixel, expected
+        );
+    }
+}
+
+#[test]
+fn next_placement_with_the_default_theme() {
+    check("next.png", Theme::default(), false);
+}
+
+#[test]
+fn full_path_filled_in_game_colours() {
+    let theme = Theme {
+        palette: Palette::Game,
+        style: PlanStyle::Filled,
+        step_numbers: true,
+        ..Theme::default()
+    };
+    check("full_path.png", theme, true);
+}
+
+#[test]
+fn colorblind_outline_of_the_whole_plan() {
+    let theme = Theme {
+        palette: Palette::Colorblind,
+        opacity: 0.8,
+        ..Theme::default()
+    };
+    check("colorblind.png", theme, true);
+}
+
+#[test]
+fn step_numbers_are_drawn_over_their_placements() {
+    let renderer = Renderer::new(1920, 1080, FieldPlacement::PLAYER_1);
+    let mut overlay = Overlay::new(renderer.field);
+    overlay.full_path = true;
+    overlay.theme.step_numbers = true;
+    overlay.handle(OverlayEvent::Plan(Arc::new(plan().first().copied())));
+    overlay.handle(OverlayEvent::Path(plan()));
+    let mut canvas = renderer.canvas([0, 0, 0, 255]);
+    overlay.draw(&mut canvas);
+
+    let layout = renderer.layout;
+    for (i, step) in plan().iter().enumerate() {
+        let number = (i + 1).to_string();
+        let text = canvas
+            .texts
+            .iter()
+            .find(|text| text.text == number)
+            .unwrap_or_else(|| panic!("no number {} in {:?}", number, canvas.texts));
+        // Text is placed in client pixels, the same space as the sprites.
+        let rects: Vec<_> = step
+            .cells
+            .iter()
+            .map(|&(x, y)| layout.cell_rect(&renderer.field, x, y))
+            .collect();
+        let left = rects.iter().map(|r| r.x).fold(f32::MAX, f32::min);
+        let right = rects.iter().map(|r| r.x + r.width).fold(f32::MIN, f32::max);
+        let top = rects.iter().map(|r| r.y).fold(f32::MAX, f32::min);
+        let bottom = rects
+            .iter()
+            .map(|r| r.y + r.height)
+            .fold(f32::MIN, f32::max);
+        assert!(
+            left < text.x && text.x < right && top < text.y && text.y < bottom,
+            "{:?} is outside {:?}",
+            text,
+            rects
+        );
+        assert!((text.size - 0.6 * layout.pixels_per_cell()).abs() < 1e-3);
+    }
+}